@@ -0,0 +1,137 @@
+//! Validates shaders offline with naga, without a GPU. Exits with a non-zero status if any
+//! shader fails to load or validate.
+
+use std::{path::Path, process::ExitCode};
+
+use naga::valid::Capabilities;
+use wgputil::shader::ShaderSource;
+
+const USAGE: &str = "\
+Usage: wgputil-check [OPTIONS] <SHADER>...
+
+Shaders ending in .spv are loaded as SPIR-V, .vert/.frag/.comp as GLSL (with the `glsl`
+feature), everything else as WGSL.
+
+Options:
+  -D, --define NAME[=VALUE]   Define for the WGSL or GLSL preprocessor, may be repeated
+  -c, --capabilities LIST     Comma-separated naga capabilities (e.g. PUSH_CONSTANT,FLOAT64),
+                              or `all`. Defaults to naga's default capabilities
+  -h, --help                  Print this message";
+
+struct Args {
+    defines: Vec<(String, String)>,
+    capabilities: Capabilities,
+    shaders: Vec<String>,
+}
+
+fn parse_capabilities(list: &str) -> Result<Capabilities, String> {
+    if list.eq_ignore_ascii_case("all") {
+        return Ok(Capabilities::all());
+    }
+
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .try_fold(Capabilities::empty(), |capabilities, name| {
+            Capabilities::from_name(&name.to_ascii_uppercase())
+                .map(|c| capabilities | c)
+                .ok_or_else(|| format!("unknown capability {name}"))
+        })
+}
+
+fn parse_args() -> Result<Option<Args>, String> {
+    let mut args = Args {
+        defines: Vec::new(),
+        capabilities: Capabilities::default(),
+        shaders: Vec::new(),
+    };
+
+    let mut iter = std::env::args().skip(1);
+
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .ok_or_else(|| format!("missing value for {name}"))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-D" | "--define" => {
+                let define = value(&arg)?;
+                let (name, value) = define.split_once('=').unwrap_or((&define, ""));
+                args.defines.push((name.to_owned(), value.to_owned()));
+            }
+            "-c" | "--capabilities" => args.capabilities = parse_capabilities(&value(&arg)?)?,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ => args.shaders.push(arg),
+        }
+    }
+
+    if args.shaders.is_empty() {
+        return Err("no shaders given".to_owned());
+    }
+
+    Ok(Some(args))
+}
+
+fn load(path: &str, defines: &[(String, String)]) -> Result<ShaderSource, String> {
+    Ok(match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("spv") => ShaderSource::load_spirv(path),
+        #[cfg(feature = "glsl")]
+        Some("vert") => ShaderSource::load_glsl(path, naga::ShaderStage::Vertex, defines.to_vec()),
+        #[cfg(feature = "glsl")]
+        Some("frag") => {
+            ShaderSource::load_glsl(path, naga::ShaderStage::Fragment, defines.to_vec())
+        }
+        #[cfg(feature = "glsl")]
+        Some("comp") => ShaderSource::load_glsl(path, naga::ShaderStage::Compute, defines.to_vec()),
+        #[cfg(not(feature = "glsl"))]
+        Some("vert" | "frag" | "comp") => {
+            return Err(format!(
+                "failed to load {path}: GLSL support requires the `glsl` feature"
+            ))
+        }
+        _ => ShaderSource::load_wgsl_with_defines(path, defines.iter().cloned()),
+    })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut failed = 0;
+
+    for path in &args.shaders {
+        let result = load(path, &args.defines).and_then(|source| match source.load_error() {
+            Some(e) => Err(format!("failed to load {path}: {e}")),
+            None => source
+                .validate(args.capabilities)
+                .map(|_| source)
+                .map_err(|e| e.to_string()),
+        });
+
+        match result {
+            Ok(source) => println!("ok: {}", source.name()),
+            Err(e) => {
+                eprintln!("{e}");
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{failed} of {} shaders failed", args.shaders.len());
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
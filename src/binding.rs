@@ -1,5 +1,18 @@
 use std::num::NonZero;
 
+use crate::{texture, Error, TextureError};
+
+mod bindless;
+mod cache;
+mod validate;
+
+pub use bindless::{BindlessTable, BindlessTableDescriptor, SamplerHandle, TextureHandle};
+pub use cache::{create_linked_cached, BindGroupCache, BindGroupLayoutCache};
+pub use validate::{validate_against, BindingMismatch, MismatchKind};
+
+#[cfg(feature = "derive")]
+pub use wgputil_derive::BindGroup;
+
 pub fn bind_buffer_uniform(buffer: &wgpu::Buffer) -> BindingEntry<'_> {
     BindingEntry {
         binding_type: wgpu::BindingType::Buffer {
@@ -12,6 +25,27 @@ pub fn bind_buffer_uniform(buffer: &wgpu::Buffer) -> BindingEntry<'_> {
     }
 }
 
+/// Binds a window of `binding_size` bytes, whose position is given as a dynamic offset when
+/// setting the bind group.
+pub fn bind_buffer_uniform_dynamic(
+    buffer: &wgpu::Buffer,
+    binding_size: wgpu::BufferSize,
+) -> BindingEntry<'_> {
+    BindingEntry {
+        binding_type: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: true,
+            min_binding_size: Some(binding_size),
+        },
+        count: None,
+        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer,
+            offset: 0,
+            size: Some(binding_size),
+        }),
+    }
+}
+
 pub fn bind_buffer_storage(buffer: &wgpu::Buffer, read_only: bool) -> BindingEntry<'_> {
     BindingEntry {
         binding_type: wgpu::BindingType::Buffer {
@@ -40,6 +74,69 @@ pub fn bind_texture(
     }
 }
 
+pub fn bind_texture_multisampled(
+    view: &wgpu::TextureView,
+    sample_type: wgpu::TextureSampleType,
+    view_dimension: wgpu::TextureViewDimension,
+) -> BindingEntry<'_> {
+    BindingEntry {
+        binding_type: wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension,
+            multisampled: true,
+        },
+        count: None,
+        resource: wgpu::BindingResource::TextureView(view),
+    }
+}
+
+/// Binds a depth texture, to be sampled with a comparison sampler or read with `textureLoad`.
+pub fn bind_depth_texture(
+    view: &wgpu::TextureView,
+    view_dimension: wgpu::TextureViewDimension,
+) -> BindingEntry<'_> {
+    bind_texture(view, wgpu::TextureSampleType::Depth, view_dimension)
+}
+
+/// Binds a texture, deriving the sample type, view dimension and multisampling from the texture
+/// itself. The view is assumed to cover the whole texture; 2D textures with multiple layers are
+/// bound as `D2Array`, so use [`bind_texture`] for cube views.
+pub fn bind_texture_auto<'a>(
+    device: &wgpu::Device,
+    texture: &wgpu::Texture,
+    view: &'a wgpu::TextureView,
+) -> Result<BindingEntry<'a>, Error> {
+    let multisampled = texture.sample_count() > 1;
+
+    let sample_type = match texture::sample_type(device, texture) {
+        // Multisampled float textures can't be filtered
+        Some(wgpu::TextureSampleType::Float { .. }) if multisampled => {
+            wgpu::TextureSampleType::Float { filterable: false }
+        }
+        Some(sample_type) => sample_type,
+        None => return Err(TextureError::InvalidFormat(texture.format()).into()),
+    };
+
+    let view_dimension = match texture.dimension() {
+        wgpu::TextureDimension::D1 => wgpu::TextureViewDimension::D1,
+        wgpu::TextureDimension::D2 if texture.depth_or_array_layers() > 1 => {
+            wgpu::TextureViewDimension::D2Array
+        }
+        wgpu::TextureDimension::D2 => wgpu::TextureViewDimension::D2,
+        wgpu::TextureDimension::D3 => wgpu::TextureViewDimension::D3,
+    };
+
+    Ok(BindingEntry {
+        binding_type: wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension,
+            multisampled,
+        },
+        count: None,
+        resource: wgpu::BindingResource::TextureView(view),
+    })
+}
+
 pub fn bind_textures<'a>(
     views: &'a [&wgpu::TextureView],
     sample_type: wgpu::TextureSampleType,
@@ -101,6 +198,10 @@ pub fn bind_sampler(
     }
 }
 
+pub fn bind_comparison_sampler(sampler: &wgpu::Sampler) -> BindingEntry<'_> {
+    bind_sampler(sampler, wgpu::SamplerBindingType::Comparison)
+}
+
 pub fn bind_samplers<'a>(
     samplers: &'a [&wgpu::Sampler],
     binding_type: wgpu::SamplerBindingType,
@@ -112,6 +213,17 @@ pub fn bind_samplers<'a>(
     }
 }
 
+/// Requires [`wgpu::Features::EXPERIMENTAL_RAY_QUERY`].
+pub fn bind_acceleration_structure(tlas: &wgpu::Tlas) -> BindingEntry<'_> {
+    BindingEntry {
+        binding_type: wgpu::BindingType::AccelerationStructure {
+            vertex_return: false,
+        },
+        count: None,
+        resource: wgpu::BindingResource::AccelerationStructure(tlas),
+    }
+}
+
 /// Entry for creating a linked [`wgpu::BindGroupLayoutEntry`] and [`wgpu::BindGroupEntry`].
 pub struct BindingEntry<'a> {
     pub binding_type: wgpu::BindingType,
@@ -123,12 +235,7 @@ pub struct BindingEntry<'a> {
 impl<'a> BindingEntry<'a> {
     pub fn build(&self, index: usize) -> (wgpu::BindGroupLayoutEntry, wgpu::BindGroupEntry<'a>) {
         (
-            wgpu::BindGroupLayoutEntry {
-                binding: index as u32,
-                visibility: wgpu::ShaderStages::all(), // only a thing on directx, skip specifying
-                ty: self.binding_type,
-                count: self.count.map(|c| c as u32).and_then(NonZero::new),
-            },
+            layout_entry(index, self.binding_type, self.count),
             wgpu::BindGroupEntry {
                 binding: index as u32,
                 resource: self.resource.clone(),
@@ -137,6 +244,59 @@ impl<'a> BindingEntry<'a> {
     }
 }
 
+/// Create the [`wgpu::BindGroupLayoutEntry`] that [`BindingEntry::build`] would, without needing a resource.
+pub fn layout_entry(
+    index: usize,
+    binding_type: wgpu::BindingType,
+    count: Option<usize>,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: index as u32,
+        visibility: wgpu::ShaderStages::all(), // only a thing on directx, skip specifying
+        ty: binding_type,
+        count: count.map(|c| c as u32).and_then(NonZero::new),
+    }
+}
+
+/// A struct whose fields are bound in sequential order as a bind group. Usually implemented with
+/// `#[derive(BindGroup)]`, available with the `derive` feature.
+pub trait AsBindGroup {
+    /// Layout entries, which don't depend on the bound resources.
+    fn layout_entries() -> Vec<wgpu::BindGroupLayoutEntry>;
+
+    fn binding_entries(&self) -> Vec<BindingEntry<'_>>;
+
+    fn create_bind_group_layout(device: &wgpu::Device, label: &str) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &Self::layout_entries(),
+        })
+    }
+
+    fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        let resources: Vec<_> = self
+            .binding_entries()
+            .into_iter()
+            .map(|e| e.resource)
+            .collect();
+
+        create_sequential_with_layout(device, label, layout, &resources)
+    }
+
+    fn create_linked(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+    ) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
+        create_sequential_linked(device, label, &self.binding_entries())
+    }
+}
+
 /// Create a [`wgpu::BindGroup`] along with its corresponding [`wgpu::BindGroupLayout`], with sequential binding indices.
 pub fn create_sequential_linked(
     device: &wgpu::Device,
@@ -184,3 +344,59 @@ pub fn create_sequential_with_layout(
         entries: &bind_group_entries,
     })
 }
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+
+    #[derive(BindGroup)]
+    #[allow(dead_code)]
+    struct BlurBindings<'a> {
+        #[uniform(0)]
+        params: &'a wgpu::Buffer,
+        #[texture(1, sample = float, dim = d2)]
+        input: &'a wgpu::TextureView,
+        #[sampler(2)]
+        sampler: &'a wgpu::Sampler,
+        #[storage_texture(3, format = Rgba16Float, access = write, dim = d2)]
+        output: &'a wgpu::TextureView,
+        #[storage(4, read_only)]
+        weights: &'a wgpu::Buffer,
+    }
+
+    #[test]
+    fn derived_layout_entries() {
+        let entries = BlurBindings::layout_entries();
+
+        let bindings: Vec<_> = entries.iter().map(|e| e.binding).collect();
+        assert_eq!(bindings, [0, 1, 2, 3, 4]);
+
+        let types: Vec<_> = entries.iter().map(|e| e.ty).collect();
+        assert_eq!(
+            types,
+            [
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+            ]
+        );
+    }
+}
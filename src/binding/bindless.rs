@@ -0,0 +1,242 @@
+use crate::{BindingError, Error};
+
+use super::{bind_samplers, bind_textures};
+
+/// Index of a texture in a [`BindlessTable`], to be uploaded for indexing the texture array
+/// in the shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(u32);
+
+impl TextureHandle {
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+/// Index of a sampler in a [`BindlessTable`], to be uploaded for indexing the sampler array
+/// in the shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerHandle(u32);
+
+impl SamplerHandle {
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+/// Fixed-size list of slots that hands out the lowest free index first.
+struct Slots<T> {
+    slots: Vec<Option<T>>,
+    free: Vec<u32>,
+}
+
+impl<T> Slots<T> {
+    fn new(capacity: u32) -> Self {
+        Self {
+            slots: (0..capacity).map(|_| None).collect(),
+            free: (0..capacity).rev().collect(),
+        }
+    }
+
+    fn insert(&mut self, value: T) -> Option<u32> {
+        let index = self.free.pop()?;
+        self.slots[index as usize] = Some(value);
+
+        Some(index)
+    }
+
+    fn remove(&mut self, index: u32) -> Option<T> {
+        let value = self.slots.get_mut(index as usize)?.take()?;
+        self.free.push(index);
+
+        Some(value)
+    }
+
+    fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+}
+
+pub struct BindlessTableDescriptor<'a> {
+    pub label: &'a str,
+
+    pub texture_capacity: u32,
+    pub sample_type: wgpu::TextureSampleType,
+    pub view_dimension: wgpu::TextureViewDimension,
+
+    pub sampler_capacity: u32,
+    pub sampler_binding_type: wgpu::SamplerBindingType,
+
+    /// Bound in every unused texture slot. Must match the sample type and view dimension.
+    pub default_view: &'a wgpu::TextureView,
+    /// Bound in every unused sampler slot. Must match the sampler binding type.
+    pub default_sampler: &'a wgpu::Sampler,
+}
+
+/// Manages a growing set of texture views and samplers bound as two binding arrays
+/// (binding 0 for textures, binding 1 for samplers), handing out stable indices for them.
+///
+/// The bind group is only recreated when the contents changed since it was last requested.
+pub struct BindlessTable {
+    label: String,
+
+    textures: Slots<wgpu::TextureView>,
+    samplers: Slots<wgpu::Sampler>,
+
+    default_view: wgpu::TextureView,
+    default_sampler: wgpu::Sampler,
+
+    layout: wgpu::BindGroupLayout,
+
+    /// `None` if the contents changed since the bind group was last built.
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl BindlessTable {
+    /// Creates the table and its layout. Requires [`wgpu::Features::TEXTURE_BINDING_ARRAY`], and
+    /// capacities within the device's binding array limits.
+    pub fn new(device: &wgpu::Device, desc: &BindlessTableDescriptor) -> Result<Self, Error> {
+        let features = device.features();
+
+        if !features.contains(wgpu::Features::TEXTURE_BINDING_ARRAY) {
+            return Err(Error::MissingFeatures(
+                wgpu::Features::TEXTURE_BINDING_ARRAY,
+            ));
+        }
+
+        if !features
+            .contains(wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING)
+        {
+            log::warn!(
+                "Bindless table {} created without non-uniform indexing support, array indices must be uniform",
+                desc.label
+            );
+        }
+
+        let limits = device.limits();
+
+        let checks = [
+            (
+                desc.texture_capacity,
+                limits.max_binding_array_elements_per_shader_stage,
+            ),
+            (
+                desc.sampler_capacity,
+                limits.max_binding_array_sampler_elements_per_shader_stage,
+            ),
+        ];
+
+        for (count, limit) in checks {
+            if count == 0 || count > limit {
+                return Err(BindingError::InvalidArrayCount { count, limit }.into());
+            }
+        }
+
+        let views = vec![desc.default_view; desc.texture_capacity as usize];
+        let samplers = vec![desc.default_sampler; desc.sampler_capacity as usize];
+
+        let (texture_layout_entry, _) =
+            bind_textures(&views, desc.sample_type, desc.view_dimension).build(0);
+        let (sampler_layout_entry, _) =
+            bind_samplers(&samplers, desc.sampler_binding_type).build(1);
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(desc.label),
+            entries: &[texture_layout_entry, sampler_layout_entry],
+        });
+
+        Ok(Self {
+            label: desc.label.to_owned(),
+            textures: Slots::new(desc.texture_capacity),
+            samplers: Slots::new(desc.sampler_capacity),
+            default_view: desc.default_view.clone(),
+            default_sampler: desc.default_sampler.clone(),
+            layout,
+            bind_group: None,
+        })
+    }
+
+    pub fn insert_texture(&mut self, view: &wgpu::TextureView) -> Result<TextureHandle, Error> {
+        let index = self
+            .textures
+            .insert(view.clone())
+            .ok_or_else(|| BindingError::TableFull(self.label.clone()))?;
+
+        self.bind_group = None;
+        Ok(TextureHandle(index))
+    }
+
+    /// Frees the slot, which may be handed out again by the next insertion. Returns the view
+    /// that was stored there, if any.
+    pub fn remove_texture(&mut self, handle: TextureHandle) -> Option<wgpu::TextureView> {
+        let view = self.textures.remove(handle.0)?;
+
+        self.bind_group = None;
+        Some(view)
+    }
+
+    pub fn insert_sampler(&mut self, sampler: &wgpu::Sampler) -> Result<SamplerHandle, Error> {
+        let index = self
+            .samplers
+            .insert(sampler.clone())
+            .ok_or_else(|| BindingError::TableFull(self.label.clone()))?;
+
+        self.bind_group = None;
+        Ok(SamplerHandle(index))
+    }
+
+    /// Frees the slot, which may be handed out again by the next insertion. Returns the sampler
+    /// that was stored there, if any.
+    pub fn remove_sampler(&mut self, handle: SamplerHandle) -> Option<wgpu::Sampler> {
+        let sampler = self.samplers.remove(handle.0)?;
+
+        self.bind_group = None;
+        Some(sampler)
+    }
+
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn sampler_count(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    /// Returns whether the next call to [`BindlessTable::bind_group`] will create a new bind group.
+    pub fn is_dirty(&self) -> bool {
+        self.bind_group.is_none()
+    }
+
+    /// Returns the bind group, recreating it first if any slot changed.
+    pub fn bind_group(&mut self, device: &wgpu::Device) -> &wgpu::BindGroup {
+        self.bind_group.get_or_insert_with(|| {
+            let views: Vec<_> = self
+                .textures
+                .slots
+                .iter()
+                .map(|slot| slot.as_ref().unwrap_or(&self.default_view))
+                .collect();
+
+            let samplers: Vec<_> = self
+                .samplers
+                .slots
+                .iter()
+                .map(|slot| slot.as_ref().unwrap_or(&self.default_sampler))
+                .collect();
+
+            super::create_sequential_with_layout(
+                device,
+                &self.label,
+                &self.layout,
+                &[
+                    wgpu::BindingResource::TextureViewArray(&views),
+                    wgpu::BindingResource::SamplerArray(&samplers),
+                ],
+            )
+        })
+    }
+}
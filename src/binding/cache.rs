@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use super::BindingEntry;
+
+/// Deduplicates [`wgpu::BindGroupLayout`]s by their entries, so that structurally identical
+/// layouts created from different places are the same object and can be shared between
+/// pipelines and bind groups.
+#[derive(Default)]
+pub struct BindGroupLayoutCache {
+    layouts: HashMap<Vec<wgpu::BindGroupLayoutEntry>, wgpu::BindGroupLayout>,
+}
+
+impl BindGroupLayoutCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the cached layout for these entries, creating it if it doesn't exist yet.
+    /// The label is only used when the layout is created.
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        entries: &[wgpu::BindGroupLayoutEntry],
+    ) -> wgpu::BindGroupLayout {
+        if let Some(layout) = self.layouts.get(entries) {
+            return layout.clone();
+        }
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries,
+        });
+
+        self.layouts.insert(entries.to_vec(), layout.clone());
+        layout
+    }
+
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+
+    /// Drops all cached layouts. Layouts still in use elsewhere stay alive.
+    pub fn clear(&mut self) {
+        self.layouts.clear();
+    }
+}
+
+/// Like [`super::create_sequential_linked`], but the [`wgpu::BindGroupLayout`] is taken from
+/// the cache, so calls with the same binding types return the same layout.
+pub fn create_linked_cached(
+    device: &wgpu::Device,
+    cache: &mut BindGroupLayoutCache,
+    label: &str,
+    entries: &[BindingEntry],
+) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
+    let built_entries = entries.iter().enumerate().map(|(i, e)| e.build(i));
+
+    let bind_group_layout_entries: Vec<_> = built_entries.clone().map(|(e, _)| e).collect();
+    let bind_group_entries: Vec<_> = built_entries.map(|(_, e)| e).collect();
+
+    let bind_group_layout = cache.get_or_create(device, label, &bind_group_layout_entries);
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(label),
+        layout: &bind_group_layout,
+        entries: &bind_group_entries,
+    });
+
+    (bind_group_layout, bind_group)
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct BufferKey {
+    buffer: wgpu::Buffer,
+    offset: u64,
+    size: Option<wgpu::BufferSize>,
+}
+
+impl From<&wgpu::BufferBinding<'_>> for BufferKey {
+    fn from(binding: &wgpu::BufferBinding<'_>) -> Self {
+        Self {
+            buffer: binding.buffer.clone(),
+            offset: binding.offset,
+            size: binding.size,
+        }
+    }
+}
+
+/// Identity of a bound resource. wgpu handles compare and hash by identity, so holding
+/// clones is enough to tell whether a resource was replaced.
+#[derive(PartialEq, Eq, Hash)]
+enum ResourceKey {
+    Buffer(BufferKey),
+    BufferArray(Vec<BufferKey>),
+    Sampler(wgpu::Sampler),
+    SamplerArray(Vec<wgpu::Sampler>),
+    TextureView(wgpu::TextureView),
+    TextureViewArray(Vec<wgpu::TextureView>),
+}
+
+impl ResourceKey {
+    /// Returns `None` for resources that can't be cached (e.g. acceleration structures).
+    fn new(resource: &wgpu::BindingResource) -> Option<Self> {
+        Some(match resource {
+            wgpu::BindingResource::Buffer(binding) => Self::Buffer(binding.into()),
+            wgpu::BindingResource::BufferArray(bindings) => {
+                Self::BufferArray(bindings.iter().map(BufferKey::from).collect())
+            }
+            wgpu::BindingResource::Sampler(sampler) => Self::Sampler((*sampler).clone()),
+            wgpu::BindingResource::SamplerArray(samplers) => {
+                Self::SamplerArray(samplers.iter().map(|&s| s.clone()).collect())
+            }
+            wgpu::BindingResource::TextureView(view) => Self::TextureView((*view).clone()),
+            wgpu::BindingResource::TextureViewArray(views) => {
+                Self::TextureViewArray(views.iter().map(|&v| v.clone()).collect())
+            }
+            _ => return None,
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct BindGroupKey {
+    layout: wgpu::BindGroupLayout,
+    resources: Vec<ResourceKey>,
+}
+
+struct CachedBindGroup {
+    bind_group: wgpu::BindGroup,
+
+    /// Access counter value at the last use, for LRU eviction.
+    last_access: u64,
+    /// Frame of the last use, for garbage collection.
+    last_frame: u64,
+}
+
+/// Caches [`wgpu::BindGroup`]s by their layout and the identity of the bound resources, so
+/// bind groups can be "rebuilt" every frame and only actually created when an input changed.
+///
+/// Cached bind groups keep their resources alive. Call [`BindGroupCache::next_frame`] once per
+/// frame so that bind groups that haven't been used in a while (along with their resources)
+/// are released.
+pub struct BindGroupCache {
+    entries: HashMap<BindGroupKey, CachedBindGroup>,
+
+    capacity: usize,
+    max_unused_frames: u64,
+
+    access: u64,
+    frame: u64,
+}
+
+impl BindGroupCache {
+    /// Creates a cache holding at most `capacity` bind groups, which drops bind groups that
+    /// haven't been used for more than `max_unused_frames` frames.
+    pub fn new(capacity: usize, max_unused_frames: u64) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+            max_unused_frames,
+            access: 0,
+            frame: 0,
+        }
+    }
+
+    /// Cached equivalent of [`super::create_sequential_with_layout`]. The label is only used
+    /// when the bind group is created.
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        layout: &wgpu::BindGroupLayout,
+        entries: &[wgpu::BindingResource],
+    ) -> wgpu::BindGroup {
+        let resources: Option<Vec<_>> = entries.iter().map(ResourceKey::new).collect();
+
+        let Some(resources) = resources else {
+            return super::create_sequential_with_layout(device, label, layout, entries);
+        };
+
+        let key = BindGroupKey {
+            layout: layout.clone(),
+            resources,
+        };
+
+        self.access += 1;
+
+        if let Some(cached) = self.entries.get_mut(&key) {
+            cached.last_access = self.access;
+            cached.last_frame = self.frame;
+
+            return cached.bind_group.clone();
+        }
+
+        if self.entries.len() >= self.capacity {
+            self.evict_least_recently_used();
+        }
+
+        let bind_group = super::create_sequential_with_layout(device, label, layout, entries);
+
+        self.entries.insert(
+            key,
+            CachedBindGroup {
+                bind_group: bind_group.clone(),
+                last_access: self.access,
+                last_frame: self.frame,
+            },
+        );
+
+        bind_group
+    }
+
+    /// Advances the frame counter and drops bind groups that have gone unused for too long.
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+
+        let frame = self.frame;
+        let max_unused_frames = self.max_unused_frames;
+
+        self.entries
+            .retain(|_, cached| frame - cached.last_frame <= max_unused_frames);
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        // Access counter values are unique, so this identifies a single entry
+        let oldest = self.entries.values().map(|cached| cached.last_access).min();
+
+        if let Some(oldest) = oldest {
+            self.entries
+                .retain(|_, cached| cached.last_access != oldest);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,419 @@
+use std::fmt;
+
+use crate::{shader::ShaderSource, Error};
+
+use super::BindingEntry;
+
+/// A single disagreement between a [`BindingEntry`] and the binding declared by a shader.
+#[derive(Clone, Debug)]
+pub struct BindingMismatch {
+    pub shader: String,
+    pub group: u32,
+    pub binding: u32,
+    pub kind: MismatchKind,
+}
+
+/// The kind of [`BindingMismatch`]. "Expected" always refers to what the shader declares,
+/// "found" to what the [`BindingEntry`] provides.
+#[derive(Clone, Debug)]
+pub enum MismatchKind {
+    /// The shader declares a binding that has no corresponding entry.
+    Missing,
+    WrongType {
+        expected: String,
+        found: wgpu::BindingType,
+    },
+    WrongSampleType {
+        expected: String,
+        found: wgpu::TextureSampleType,
+    },
+    WrongViewDimension {
+        expected: wgpu::TextureViewDimension,
+        found: wgpu::TextureViewDimension,
+    },
+    WrongMultisampled {
+        expected: bool,
+    },
+    /// `None` means a single resource, not a binding array.
+    WrongArrayCount {
+        expected: Option<u32>,
+        found: Option<usize>,
+    },
+    StorageAccess {
+        expected: naga::StorageAccess,
+        found: String,
+    },
+    WrongStorageFormat {
+        expected: naga::StorageFormat,
+        found: wgpu::TextureFormat,
+    },
+}
+
+impl fmt::Display for BindingMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} @group({}) @binding({}): ",
+            self.shader, self.group, self.binding
+        )?;
+
+        match &self.kind {
+            MismatchKind::Missing => write!(f, "declared in the shader but no entry was provided"),
+            MismatchKind::WrongType { expected, found } => {
+                write!(f, "shader declares {expected}, entry is {found:?}")
+            }
+            MismatchKind::WrongSampleType { expected, found } => {
+                write!(
+                    f,
+                    "shader samples {expected}, entry sample type is {found:?}"
+                )
+            }
+            MismatchKind::WrongViewDimension { expected, found } => {
+                write!(f, "shader declares a {expected:?} view, entry is {found:?}")
+            }
+            MismatchKind::WrongMultisampled { expected } => match expected {
+                true => write!(
+                    f,
+                    "shader declares a multisampled texture, entry is not multisampled"
+                ),
+                false => write!(f, "entry is multisampled, shader texture is not"),
+            },
+            MismatchKind::WrongArrayCount { expected, found } => write!(
+                f,
+                "shader declares {}, entry has {}",
+                describe_count(expected.map(|c| c as usize)),
+                describe_count(*found)
+            ),
+            MismatchKind::StorageAccess { expected, found } => {
+                write!(f, "shader requires {expected:?} access, entry is {found}")
+            }
+            MismatchKind::WrongStorageFormat { expected, found } => {
+                write!(f, "shader declares format {expected:?}, entry is {found:?}")
+            }
+        }
+    }
+}
+
+fn describe_count(count: Option<usize>) -> String {
+    match count {
+        Some(c) => format!("a binding array of {c}"),
+        None => "a single resource".to_owned(),
+    }
+}
+
+/// Checks a list of sequentially-bound entries (as passed to [`super::create_sequential_linked`])
+/// against the bindings the shader declares in `group`, using naga reflection.
+///
+/// Entries that the shader doesn't use are not reported, since wgpu allows layouts to contain
+/// more bindings than the shader needs. An empty list means the entries are compatible.
+pub fn validate_against(
+    source: &ShaderSource,
+    group: u32,
+    entries: &[BindingEntry],
+) -> Result<Vec<BindingMismatch>, Error> {
+    let module = source.reflect()?;
+
+    let mut mismatches = Vec::new();
+
+    for (_, global) in module.global_variables.iter() {
+        let Some(binding) = &global.binding else {
+            continue;
+        };
+
+        if binding.group != group {
+            continue;
+        }
+
+        let mismatch = |kind| BindingMismatch {
+            shader: source.name().to_owned(),
+            group,
+            binding: binding.binding,
+            kind,
+        };
+
+        let Some(entry) = entries.get(binding.binding as usize) else {
+            mismatches.push(mismatch(MismatchKind::Missing));
+            continue;
+        };
+
+        // Binding arrays are checked for their count, then the element type is compared
+        let inner = match &module.types[global.ty].inner {
+            naga::TypeInner::BindingArray { base, size } => {
+                let count = match size {
+                    naga::ArraySize::Constant(c) => Some(c.get()),
+                    _ => None,
+                };
+
+                let matches = match count {
+                    Some(c) => entry.count == Some(c as usize),
+                    // Runtime-sized or override-sized, any array size is fine
+                    None => entry.count.is_some(),
+                };
+
+                if !matches {
+                    mismatches.push(mismatch(MismatchKind::WrongArrayCount {
+                        expected: count,
+                        found: entry.count,
+                    }));
+                }
+
+                &module.types[*base].inner
+            }
+            inner => {
+                if entry.count.is_some() {
+                    mismatches.push(mismatch(MismatchKind::WrongArrayCount {
+                        expected: None,
+                        found: entry.count,
+                    }));
+                }
+
+                inner
+            }
+        };
+
+        mismatches.extend(
+            compare_binding(global.space, inner, &entry.binding_type)
+                .into_iter()
+                .map(mismatch),
+        );
+    }
+
+    Ok(mismatches)
+}
+
+fn compare_binding(
+    space: naga::AddressSpace,
+    inner: &naga::TypeInner,
+    binding_type: &wgpu::BindingType,
+) -> Vec<MismatchKind> {
+    let wrong_type = |expected: &str| {
+        vec![MismatchKind::WrongType {
+            expected: expected.to_owned(),
+            found: *binding_type,
+        }]
+    };
+
+    match space {
+        naga::AddressSpace::Uniform => match binding_type {
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                ..
+            } => vec![],
+            _ => wrong_type("a uniform buffer"),
+        },
+        naga::AddressSpace::Storage { access } => match binding_type {
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                ..
+            } => {
+                // A read-write entry is fine for a read-only declaration, but not the other way around
+                if *read_only && access.contains(naga::StorageAccess::STORE) {
+                    vec![MismatchKind::StorageAccess {
+                        expected: access,
+                        found: "read-only".to_owned(),
+                    }]
+                } else {
+                    vec![]
+                }
+            }
+            _ => wrong_type("a storage buffer"),
+        },
+        naga::AddressSpace::Handle => compare_handle(inner, binding_type),
+        _ => vec![],
+    }
+}
+
+fn compare_handle(inner: &naga::TypeInner, binding_type: &wgpu::BindingType) -> Vec<MismatchKind> {
+    let wrong_type = |expected: &str| {
+        vec![MismatchKind::WrongType {
+            expected: expected.to_owned(),
+            found: *binding_type,
+        }]
+    };
+
+    match inner {
+        naga::TypeInner::Image {
+            dim,
+            arrayed,
+            class,
+        } => {
+            let expected_dimension = view_dimension(*dim, *arrayed);
+
+            match (class, binding_type) {
+                (
+                    naga::ImageClass::Sampled { .. } | naga::ImageClass::Depth { .. },
+                    wgpu::BindingType::Texture {
+                        sample_type,
+                        view_dimension,
+                        multisampled,
+                    },
+                ) => {
+                    let mut kinds = Vec::new();
+
+                    let (expected_sample, expected_multi) = match class {
+                        naga::ImageClass::Sampled { kind, multi } => (Some(*kind), *multi),
+                        naga::ImageClass::Depth { multi } => (None, *multi),
+                        naga::ImageClass::Storage { .. } => unreachable!(),
+                    };
+
+                    let sample_matches = matches!(
+                        (expected_sample, sample_type),
+                        (
+                            Some(naga::ScalarKind::Float),
+                            wgpu::TextureSampleType::Float { .. }
+                        ) | (Some(naga::ScalarKind::Sint), wgpu::TextureSampleType::Sint)
+                            | (Some(naga::ScalarKind::Uint), wgpu::TextureSampleType::Uint)
+                            | (None, wgpu::TextureSampleType::Depth)
+                    );
+
+                    if !sample_matches {
+                        kinds.push(MismatchKind::WrongSampleType {
+                            expected: match expected_sample {
+                                Some(kind) => format!("{kind:?}").to_lowercase(),
+                                None => "depth".to_owned(),
+                            },
+                            found: *sample_type,
+                        });
+                    }
+
+                    if expected_dimension != *view_dimension {
+                        kinds.push(MismatchKind::WrongViewDimension {
+                            expected: expected_dimension,
+                            found: *view_dimension,
+                        });
+                    }
+
+                    if expected_multi != *multisampled {
+                        kinds.push(MismatchKind::WrongMultisampled {
+                            expected: expected_multi,
+                        });
+                    }
+
+                    kinds
+                }
+                (
+                    naga::ImageClass::Storage {
+                        format: expected_format,
+                        access: expected_access,
+                    },
+                    wgpu::BindingType::StorageTexture {
+                        access,
+                        format,
+                        view_dimension,
+                    },
+                ) => {
+                    let mut kinds = Vec::new();
+
+                    let (load, store) = match access {
+                        wgpu::StorageTextureAccess::ReadOnly => (true, false),
+                        wgpu::StorageTextureAccess::WriteOnly => (false, true),
+                        wgpu::StorageTextureAccess::ReadWrite
+                        | wgpu::StorageTextureAccess::Atomic => (true, true),
+                    };
+
+                    if expected_access.contains(naga::StorageAccess::LOAD) != load
+                        || expected_access.contains(naga::StorageAccess::STORE) != store
+                    {
+                        kinds.push(MismatchKind::StorageAccess {
+                            expected: *expected_access,
+                            found: format!("{access:?}"),
+                        });
+                    }
+
+                    if texture_format(*expected_format) != *format {
+                        kinds.push(MismatchKind::WrongStorageFormat {
+                            expected: *expected_format,
+                            found: *format,
+                        });
+                    }
+
+                    if expected_dimension != *view_dimension {
+                        kinds.push(MismatchKind::WrongViewDimension {
+                            expected: expected_dimension,
+                            found: *view_dimension,
+                        });
+                    }
+
+                    kinds
+                }
+                (naga::ImageClass::Storage { .. }, _) => wrong_type("a storage texture"),
+                _ => wrong_type("a sampled texture"),
+            }
+        }
+        naga::TypeInner::Sampler { comparison } => match binding_type {
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison) if *comparison => {
+                vec![]
+            }
+            wgpu::BindingType::Sampler(
+                wgpu::SamplerBindingType::Filtering | wgpu::SamplerBindingType::NonFiltering,
+            ) if !*comparison => vec![],
+            _ if *comparison => wrong_type("a comparison sampler"),
+            _ => wrong_type("a sampler"),
+        },
+        naga::TypeInner::AccelerationStructure { .. } => match binding_type {
+            wgpu::BindingType::AccelerationStructure { .. } => vec![],
+            _ => wrong_type("an acceleration structure"),
+        },
+        _ => vec![],
+    }
+}
+
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+fn texture_format(format: naga::StorageFormat) -> wgpu::TextureFormat {
+    use naga::StorageFormat as Sf;
+    use wgpu::TextureFormat as Tf;
+
+    match format {
+        Sf::R8Unorm => Tf::R8Unorm,
+        Sf::R8Snorm => Tf::R8Snorm,
+        Sf::R8Uint => Tf::R8Uint,
+        Sf::R8Sint => Tf::R8Sint,
+        Sf::R16Uint => Tf::R16Uint,
+        Sf::R16Sint => Tf::R16Sint,
+        Sf::R16Float => Tf::R16Float,
+        Sf::Rg8Unorm => Tf::Rg8Unorm,
+        Sf::Rg8Snorm => Tf::Rg8Snorm,
+        Sf::Rg8Uint => Tf::Rg8Uint,
+        Sf::Rg8Sint => Tf::Rg8Sint,
+        Sf::R32Uint => Tf::R32Uint,
+        Sf::R32Sint => Tf::R32Sint,
+        Sf::R32Float => Tf::R32Float,
+        Sf::Rg16Uint => Tf::Rg16Uint,
+        Sf::Rg16Sint => Tf::Rg16Sint,
+        Sf::Rg16Float => Tf::Rg16Float,
+        Sf::Rgba8Unorm => Tf::Rgba8Unorm,
+        Sf::Rgba8Snorm => Tf::Rgba8Snorm,
+        Sf::Rgba8Uint => Tf::Rgba8Uint,
+        Sf::Rgba8Sint => Tf::Rgba8Sint,
+        Sf::Bgra8Unorm => Tf::Bgra8Unorm,
+        Sf::Rgb10a2Uint => Tf::Rgb10a2Uint,
+        Sf::Rgb10a2Unorm => Tf::Rgb10a2Unorm,
+        Sf::Rg11b10Ufloat => Tf::Rg11b10Ufloat,
+        Sf::R64Uint => Tf::R64Uint,
+        Sf::Rg32Uint => Tf::Rg32Uint,
+        Sf::Rg32Sint => Tf::Rg32Sint,
+        Sf::Rg32Float => Tf::Rg32Float,
+        Sf::Rgba16Uint => Tf::Rgba16Uint,
+        Sf::Rgba16Sint => Tf::Rgba16Sint,
+        Sf::Rgba16Float => Tf::Rgba16Float,
+        Sf::Rgba32Uint => Tf::Rgba32Uint,
+        Sf::Rgba32Sint => Tf::Rgba32Sint,
+        Sf::Rgba32Float => Tf::Rgba32Float,
+        Sf::R16Unorm => Tf::R16Unorm,
+        Sf::R16Snorm => Tf::R16Snorm,
+        Sf::Rg16Unorm => Tf::Rg16Unorm,
+        Sf::Rg16Snorm => Tf::Rg16Snorm,
+        Sf::Rgba16Unorm => Tf::Rgba16Unorm,
+        Sf::Rgba16Snorm => Tf::Rgba16Snorm,
+    }
+}
@@ -1,12 +1,444 @@
-use std::num::NonZero;
+use std::{marker::PhantomData, num::NonZero};
 
+use wgpu::util::DeviceExt;
+
+use crate::{
+    binding::{self, BindingEntry},
+    BufferError, Error,
+};
+
+mod indirect;
+mod pool;
+mod readback;
+mod upload;
+mod vertex;
+
+pub use indirect::{IndirectArgs, IndirectBuffer};
+pub use pool::{BufferPool, PoolStats};
+pub use readback::{read_back, read_back_async, read_back_typed, ReadbackFuture, ReadbackRequest};
+pub use upload::{UploadRing, UploadStats};
+pub use vertex::{index_buffer, vertex_buffer, IndexBuffer, Vertex, VertexAttributeType};
+
+#[cfg(feature = "derive")]
+pub use wgputil_derive::Vertex;
+
+/// Writes `data` to the buffer at `offset` bytes. Empty slices are ignored.
 pub fn write_slice(queue: &wgpu::Queue, buffer: &wgpu::Buffer, data: &[u8], offset: usize) {
+    let Some(size) = NonZero::new(data.len() as u64) else {
+        return;
+    };
+
     queue
-        .write_buffer_with(
-            buffer,
-            offset as u64,
-            NonZero::new(data.len() as u64).unwrap(),
-        )
+        .write_buffer_with(buffer, offset as u64, size)
         .unwrap()
         .copy_from_slice(data);
 }
+
+/// A [`wgpu::Buffer`] holding a fixed number of `T`s.
+pub struct TypedBuffer<T> {
+    buffer: wgpu::Buffer,
+    len: usize,
+
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> TypedBuffer<T> {
+    /// Creates a zeroed buffer with room for `len` elements. `COPY_DST` is always added to the usage.
+    pub fn new(device: &wgpu::Device, usage: wgpu::BufferUsages, len: usize) -> Self {
+        let size = (len * size_of::<T>()) as u64;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            // wgpu requires buffer sizes to be a multiple of the copy alignment
+            size: size.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a buffer initialized with `data`. `COPY_DST` is always added to the usage.
+    pub fn from_slice(device: &wgpu::Device, usage: wgpu::BufferUsages, data: &[T]) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(data),
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            buffer,
+            len: data.len(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Writes `data` starting at element `index`. The byte offset and size of the write must be
+    /// multiples of [`wgpu::COPY_BUFFER_ALIGNMENT`].
+    pub fn write(&self, queue: &wgpu::Queue, index: usize, data: &[T]) -> Result<(), Error> {
+        if index + data.len() > self.len {
+            return Err(BufferError::OutOfBounds {
+                index,
+                len: data.len(),
+                capacity: self.len,
+            }
+            .into());
+        }
+
+        let Some(size) = NonZero::new(size_of_val(data) as u64) else {
+            return Ok(());
+        };
+
+        let offset = (index * size_of::<T>()) as u64;
+
+        if !offset.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+            || !size.get().is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+        {
+            return Err(BufferError::Misaligned {
+                offset,
+                size: size.get(),
+            }
+            .into());
+        }
+
+        queue
+            .write_buffer_with(&self.buffer, offset, size)
+            .ok_or(BufferError::WriteFailed)?
+            .copy_from_slice(bytemuck::cast_slice(data));
+
+        Ok(())
+    }
+
+    /// Number of elements the buffer holds.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn bind_uniform(&self) -> BindingEntry<'_> {
+        binding::bind_buffer_uniform(&self.buffer)
+    }
+
+    pub fn bind_storage(&self, read_only: bool) -> BindingEntry<'_> {
+        binding::bind_buffer_storage(&self.buffer, read_only)
+    }
+}
+
+impl<T> AsRef<wgpu::Buffer> for TypedBuffer<T> {
+    fn as_ref(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+/// Writes `range` of `bytes` to the buffer at the same offset, widening the range to
+/// [`wgpu::COPY_BUFFER_ALIGNMENT`] and zero-padding past the end of `bytes` if needed.
+fn write_aligned(
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    bytes: &[u8],
+    range: std::ops::Range<usize>,
+) {
+    let alignment = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+
+    let start = range.start - range.start % alignment;
+    let end = range.end.next_multiple_of(alignment);
+
+    if end <= bytes.len() {
+        write_slice(queue, buffer, &bytes[start..end], start);
+    } else {
+        let mut padded = bytes[start..].to_vec();
+        padded.resize(end - start, 0);
+
+        write_slice(queue, buffer, &padded, start);
+    }
+}
+
+/// A growable list of `T`s mirrored on the GPU. Changes are made on the CPU side and only the
+/// modified range is uploaded in [`GpuVec::upload`].
+///
+/// The GPU buffer grows geometrically, so it may be replaced during an upload. Bind groups
+/// referencing [`GpuVec::buffer`] must be recreated when that happens.
+pub struct GpuVec<T> {
+    label: String,
+    usage: wgpu::BufferUsages,
+
+    data: Vec<T>,
+    dirty: Option<std::ops::Range<usize>>,
+
+    buffer: wgpu::Buffer,
+    /// Number of elements the GPU buffer has room for.
+    capacity: usize,
+}
+
+impl<T: bytemuck::Pod> GpuVec<T> {
+    /// Creates an empty vector with room for `capacity` elements on the GPU. `COPY_SRC` and
+    /// `COPY_DST` are always added to the usage, for uploads and for copying when growing.
+    pub fn new(
+        device: &wgpu::Device,
+        label: &str,
+        usage: wgpu::BufferUsages,
+        capacity: usize,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
+        let capacity = capacity.max(1);
+
+        Self {
+            label: label.to_owned(),
+            usage,
+            data: Vec::with_capacity(capacity),
+            dirty: None,
+            buffer: Self::create_buffer(device, label, usage, capacity),
+            capacity,
+        }
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        label: &str,
+        usage: wgpu::BufferUsages,
+        capacity: usize,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: ((capacity * size_of::<T>()) as u64)
+                .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn mark_dirty(&mut self, range: std::ops::Range<usize>) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+            None => range,
+        });
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.data.push(value);
+        self.mark_dirty(self.data.len() - 1..self.data.len());
+    }
+
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        let start = self.data.len();
+
+        self.data.extend_from_slice(values);
+        self.mark_dirty(start..self.data.len());
+    }
+
+    /// Returns a mutable reference to the element at `index`, marking it as modified.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.data.len() {
+            return None;
+        }
+
+        self.mark_dirty(index..index + 1);
+        self.data.get_mut(index)
+    }
+
+    /// Returns the contents as a mutable slice, marking everything as modified.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.mark_dirty(0..self.data.len());
+        &mut self.data
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Shortening doesn't require an upload; the GPU buffer keeps its stale contents past `len`.
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+
+        if let Some(dirty) = &mut self.dirty {
+            dirty.end = dirty.end.min(len);
+
+            if dirty.start >= dirty.end {
+                self.dirty = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Number of elements the GPU buffer currently has room for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Uploads modified elements, growing the GPU buffer first if it's too small. Returns `true`
+    /// if the buffer was replaced, in which case dependent bind groups must be recreated.
+    ///
+    /// When growing, the old contents are copied using `encoder`, which must be submitted with
+    /// the next [`wgpu::Queue::submit`] for the upload to be ordered correctly.
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> bool {
+        let Some(dirty) = self.dirty.take() else {
+            return false;
+        };
+
+        let bytes: &[u8] = bytemuck::cast_slice(&self.data);
+        let byte_range = |range: std::ops::Range<usize>| {
+            range.start * size_of::<T>()..range.end * size_of::<T>()
+        };
+
+        if self.data.len() <= self.capacity {
+            write_aligned(queue, &self.buffer, bytes, byte_range(dirty));
+            return false;
+        }
+
+        let old_capacity = self.capacity;
+        let new_capacity = (old_capacity * 2).max(self.data.len());
+
+        let new_buffer = Self::create_buffer(device, &self.label, self.usage, new_capacity);
+
+        // Queue writes are executed before the commands of the next submission, so modified
+        // elements that fit in the old buffer are written there and then copied along with the rest
+        if dirty.start < old_capacity {
+            write_aligned(
+                queue,
+                &self.buffer,
+                bytes,
+                byte_range(dirty.start..dirty.end.min(old_capacity)),
+            );
+        }
+
+        // The copy is executed after the write below, so it must stop before the write's
+        // aligned start to not overwrite it
+        let old_bytes = (old_capacity * size_of::<T>()) as u64;
+        let copy_size = old_bytes - old_bytes % wgpu::COPY_BUFFER_ALIGNMENT;
+
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &new_buffer, 0, copy_size);
+
+        write_aligned(
+            queue,
+            &new_buffer,
+            bytes,
+            byte_range(dirty.start.max(old_capacity)..dirty.end),
+        );
+
+        self.buffer = new_buffer;
+        self.capacity = new_capacity;
+
+        true
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn bind_storage(&self, read_only: bool) -> BindingEntry<'_> {
+        binding::bind_buffer_storage(&self.buffer, read_only)
+    }
+}
+
+/// Packs many `T`s into one uniform buffer, each at an offset aligned to the device's
+/// `min_uniform_buffer_offset_alignment`, for binding with dynamic offsets.
+///
+/// Push values during the frame, call [`DynamicUniformBuffer::upload`] before submitting, and
+/// [`DynamicUniformBuffer::reset`] at the start of the next frame.
+pub struct DynamicUniformBuffer<T> {
+    label: String,
+    alignment: u64,
+
+    data: Vec<u8>,
+
+    buffer: wgpu::Buffer,
+
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> DynamicUniformBuffer<T> {
+    /// Creates a buffer with room for `capacity` elements.
+    pub fn new(device: &wgpu::Device, label: &str, capacity: usize) -> Self {
+        assert!(size_of::<T>() > 0, "Can't bind zero-sized uniforms");
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = (size_of::<T>() as u64).next_multiple_of(alignment);
+
+        Self {
+            label: label.to_owned(),
+            alignment,
+            data: Vec::new(),
+            buffer: Self::create_buffer(device, label, stride * capacity.max(1) as u64),
+            _marker: PhantomData,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, label: &str, size: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Appends a value and returns its dynamic offset.
+    pub fn push(&mut self, value: T) -> u32 {
+        let offset = (self.data.len() as u64).next_multiple_of(self.alignment) as usize;
+
+        self.data.resize(offset, 0);
+        self.data.extend_from_slice(bytemuck::bytes_of(&value));
+
+        offset as u32
+    }
+
+    /// Removes all values, invalidating previously returned offsets.
+    pub fn reset(&mut self) {
+        self.data.clear();
+    }
+
+    /// Uploads all pushed values, growing the buffer if needed. Returns `true` if the buffer was
+    /// replaced, in which case bind groups using [`DynamicUniformBuffer::binding`] must be recreated.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        let size = (self.data.len() as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        let grown = size > self.buffer.size();
+
+        if grown {
+            self.buffer = Self::create_buffer(device, &self.label, size.next_power_of_two());
+        }
+
+        self.data.resize(size as usize, 0);
+        write_slice(queue, &self.buffer, &self.data, 0);
+
+        grown
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// A uniform binding of a single `T`, with `has_dynamic_offset` set.
+    pub fn binding(&self) -> BindingEntry<'_> {
+        binding::bind_buffer_uniform_dynamic(
+            &self.buffer,
+            wgpu::BufferSize::new(size_of::<T>() as u64).unwrap(),
+        )
+    }
+}
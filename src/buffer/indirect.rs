@@ -0,0 +1,212 @@
+use wgpu::util::{DispatchIndirectArgs, DrawIndexedIndirectArgs, DrawIndirectArgs};
+
+use crate::{
+    binding::{self, BindingEntry},
+    BufferError, Error,
+};
+
+use super::write_slice;
+
+/// Argument structs that can be stored in an [`IndirectBuffer`].
+pub trait IndirectArgs: bytemuck::Pod {}
+
+impl IndirectArgs for DrawIndirectArgs {}
+impl IndirectArgs for DrawIndexedIndirectArgs {}
+impl IndirectArgs for DispatchIndirectArgs {}
+
+/// A fixed-capacity buffer of indirect arguments, along with a `u32` count buffer for
+/// `multi_draw_*_indirect_count`. Both can be bound as storage buffers, so arguments can be
+/// written from the CPU with [`IndirectBuffer::push`] or generated by compute shaders.
+pub struct IndirectBuffer<T> {
+    buffer: wgpu::Buffer,
+    count_buffer: wgpu::Buffer,
+
+    args: Vec<T>,
+    capacity: usize,
+
+    features: wgpu::Features,
+}
+
+impl<T: IndirectArgs> IndirectBuffer<T> {
+    pub fn new(device: &wgpu::Device, label: &str, capacity: usize) -> Self {
+        let usage = wgpu::BufferUsages::INDIRECT
+            | wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity.max(1) * size_of::<T>()) as u64,
+            usage,
+            mapped_at_creation: false,
+        });
+
+        let count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size_of::<u32>() as u64,
+            usage,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            count_buffer,
+            args: Vec::with_capacity(capacity),
+            capacity,
+            features: device.features(),
+        }
+    }
+
+    pub fn push(&mut self, args: T) -> Result<(), Error> {
+        if self.args.len() >= self.capacity {
+            return Err(BufferError::OutOfBounds {
+                index: self.args.len(),
+                len: 1,
+                capacity: self.capacity,
+            }
+            .into());
+        }
+
+        self.args.push(args);
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.args.clear();
+    }
+
+    /// Number of arguments pushed from the CPU.
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Writes the pushed arguments, and their number to the count buffer. When arguments are
+    /// generated on the GPU, call this after [`IndirectBuffer::clear`] to reset the count to 0.
+    pub fn upload(&self, queue: &wgpu::Queue) {
+        write_slice(queue, &self.buffer, bytemuck::cast_slice(&self.args), 0);
+        write_slice(
+            queue,
+            &self.count_buffer,
+            bytemuck::bytes_of(&(self.args.len() as u32)),
+            0,
+        );
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn count_buffer(&self) -> &wgpu::Buffer {
+        &self.count_buffer
+    }
+
+    /// Binds the arguments as a storage buffer, for compute shaders to write them.
+    pub fn bind_storage(&self, read_only: bool) -> BindingEntry<'_> {
+        binding::bind_buffer_storage(&self.buffer, read_only)
+    }
+
+    /// Binds the count as a storage buffer, for compute shaders to increment it atomically.
+    pub fn bind_count_storage(&self, read_only: bool) -> BindingEntry<'_> {
+        binding::bind_buffer_storage(&self.count_buffer, read_only)
+    }
+
+    fn offset(index: usize) -> u64 {
+        (index * size_of::<T>()) as u64
+    }
+
+    fn check_count_feature(&self) -> Result<(), Error> {
+        match self
+            .features
+            .contains(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT)
+        {
+            true => Ok(()),
+            false => Err(Error::MissingFeatures(
+                wgpu::Features::MULTI_DRAW_INDIRECT_COUNT,
+            )),
+        }
+    }
+}
+
+impl IndirectBuffer<DrawIndirectArgs> {
+    /// Draws the arguments pushed from the CPU, in a single call if
+    /// [`wgpu::Features::MULTI_DRAW_INDIRECT`] is enabled.
+    pub fn draw(&self, pass: &mut wgpu::RenderPass) {
+        if self.features.contains(wgpu::Features::MULTI_DRAW_INDIRECT) {
+            pass.multi_draw_indirect(&self.buffer, 0, self.args.len() as u32);
+        } else {
+            for i in 0..self.args.len() {
+                pass.draw_indirect(&self.buffer, Self::offset(i));
+            }
+        }
+    }
+
+    /// Draws as many arguments as the count buffer says, up to the capacity.
+    /// Requires [`wgpu::Features::MULTI_DRAW_INDIRECT_COUNT`].
+    pub fn draw_count(&self, pass: &mut wgpu::RenderPass) -> Result<(), Error> {
+        self.check_count_feature()?;
+
+        pass.multi_draw_indirect_count(
+            &self.buffer,
+            0,
+            &self.count_buffer,
+            0,
+            self.capacity as u32,
+        );
+
+        Ok(())
+    }
+}
+
+impl IndirectBuffer<DrawIndexedIndirectArgs> {
+    /// Draws the arguments pushed from the CPU, in a single call if
+    /// [`wgpu::Features::MULTI_DRAW_INDIRECT`] is enabled.
+    pub fn draw_indexed(&self, pass: &mut wgpu::RenderPass) {
+        if self.features.contains(wgpu::Features::MULTI_DRAW_INDIRECT) {
+            pass.multi_draw_indexed_indirect(&self.buffer, 0, self.args.len() as u32);
+        } else {
+            for i in 0..self.args.len() {
+                pass.draw_indexed_indirect(&self.buffer, Self::offset(i));
+            }
+        }
+    }
+
+    /// Draws as many arguments as the count buffer says, up to the capacity.
+    /// Requires [`wgpu::Features::MULTI_DRAW_INDIRECT_COUNT`].
+    pub fn draw_indexed_count(&self, pass: &mut wgpu::RenderPass) -> Result<(), Error> {
+        self.check_count_feature()?;
+
+        pass.multi_draw_indexed_indirect_count(
+            &self.buffer,
+            0,
+            &self.count_buffer,
+            0,
+            self.capacity as u32,
+        );
+
+        Ok(())
+    }
+}
+
+impl IndirectBuffer<DispatchIndirectArgs> {
+    /// Dispatches using the arguments at `index`, which must be within the capacity.
+    pub fn dispatch(&self, pass: &mut wgpu::ComputePass, index: usize) -> Result<(), Error> {
+        if index >= self.capacity {
+            return Err(BufferError::OutOfBounds {
+                index,
+                len: 1,
+                capacity: self.capacity,
+            }
+            .into());
+        }
+
+        pass.dispatch_workgroups_indirect(&self.buffer, Self::offset(index));
+        Ok(())
+    }
+}
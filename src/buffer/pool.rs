@@ -0,0 +1,165 @@
+use std::{collections::HashMap, sync::mpsc};
+
+/// Smallest size class handed out by a [`BufferPool`], in bytes.
+const MIN_SIZE_CLASS: u64 = 256;
+
+/// Size class and usage.
+type PoolKey = (u64, wgpu::BufferUsages);
+
+/// Statistics for a single frame, as reported by [`BufferPool::stats`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PoolStats {
+    /// Buffers created because no free buffer of the right class was available.
+    pub created: usize,
+    /// Acquisitions served by a recycled buffer.
+    pub reused: usize,
+    /// Buffers destroyed by [`BufferPool::trim`].
+    pub trimmed: usize,
+
+    /// Buffers currently waiting for reuse.
+    pub free: usize,
+    /// Total size of the buffers currently waiting for reuse, in bytes.
+    pub free_bytes: u64,
+}
+
+struct FreeBuffer {
+    buffer: wgpu::Buffer,
+    /// Frame in which the buffer was returned to the pool.
+    frame: u64,
+}
+
+/// Hands out transient buffers grouped by size class (the next power of two) and usage, and
+/// takes them back once the GPU has finished the submission they were used in.
+///
+/// Each frame:
+/// 1. call [`BufferPool::acquire`] for every transient buffer,
+/// 2. submit the work using them, then call [`BufferPool::recall`],
+/// 3. call [`BufferPool::trim`] to destroy buffers that went unused for too long.
+///
+/// Acquired buffers must not be used after the submission following their acquisition.
+/// Returned buffers are only noticed when the device is polled.
+pub struct BufferPool {
+    free: HashMap<PoolKey, Vec<FreeBuffer>>,
+    /// Buffers acquired since the last recall.
+    acquired: Vec<(PoolKey, wgpu::Buffer)>,
+
+    returned_sender: mpsc::Sender<Vec<(PoolKey, wgpu::Buffer)>>,
+    returned_receiver: mpsc::Receiver<Vec<(PoolKey, wgpu::Buffer)>>,
+
+    frame: u64,
+    max_unused_frames: u64,
+
+    current: PoolStats,
+    last: PoolStats,
+}
+
+impl BufferPool {
+    /// Creates a pool that destroys free buffers after `max_unused_frames` calls to [`BufferPool::trim`].
+    pub fn new(max_unused_frames: u64) -> Self {
+        let (returned_sender, returned_receiver) = mpsc::channel();
+
+        Self {
+            free: HashMap::new(),
+            acquired: Vec::new(),
+            returned_sender,
+            returned_receiver,
+            frame: 0,
+            max_unused_frames,
+            current: PoolStats::default(),
+            last: PoolStats::default(),
+        }
+    }
+
+    /// Returns a buffer of at least `size` bytes with exactly the given usage.
+    pub fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+        size: u64,
+        usage: wgpu::BufferUsages,
+    ) -> wgpu::Buffer {
+        self.receive_returned();
+
+        let key = (size.next_power_of_two().max(MIN_SIZE_CLASS), usage);
+
+        let buffer = match self.free.get_mut(&key).and_then(Vec::pop) {
+            Some(free) => {
+                self.current.reused += 1;
+                free.buffer
+            }
+            None => {
+                self.current.created += 1;
+
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Pooled Buffer"),
+                    size: key.0,
+                    usage,
+                    mapped_at_creation: false,
+                })
+            }
+        };
+
+        self.acquired.push((key, buffer.clone()));
+        buffer
+    }
+
+    /// Schedules the buffers acquired since the last call to be reused once the GPU finishes
+    /// the most recent submission. Must be called after submitting.
+    pub fn recall(&mut self, queue: &wgpu::Queue) {
+        let acquired = std::mem::take(&mut self.acquired);
+        let sender = self.returned_sender.clone();
+
+        queue.on_submitted_work_done(move || {
+            // The receiver is only gone if the pool was dropped
+            let _ = sender.send(acquired);
+        });
+    }
+
+    fn receive_returned(&mut self) {
+        for returned in self.returned_receiver.try_iter() {
+            for (key, buffer) in returned {
+                self.free.entry(key).or_default().push(FreeBuffer {
+                    buffer,
+                    frame: self.frame,
+                });
+            }
+        }
+    }
+
+    /// Ends the frame for [`BufferPool::stats`], destroying free buffers that haven't been reused
+    /// for too long.
+    pub fn trim(&mut self) {
+        self.receive_returned();
+        self.frame += 1;
+
+        let frame = self.frame;
+        let max_unused_frames = self.max_unused_frames;
+
+        for buffers in self.free.values_mut() {
+            buffers.retain(|free| {
+                let keep = frame - free.frame <= max_unused_frames;
+
+                if !keep {
+                    free.buffer.destroy();
+                    self.current.trimmed += 1;
+                }
+
+                keep
+            });
+        }
+
+        self.free.retain(|_, buffers| !buffers.is_empty());
+        self.last = std::mem::take(&mut self.current);
+    }
+
+    /// Returns the statistics of the frame ended by the last [`BufferPool::trim`], along with
+    /// the buffers currently waiting for reuse.
+    pub fn stats(&self) -> PoolStats {
+        let free = self.free.values().flatten();
+
+        PoolStats {
+            free: free.clone().count(),
+            free_bytes: free.map(|f| f.buffer.size()).sum(),
+            ..self.last
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    future::Future,
+    ops::Range,
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crate::{BufferError, Error, GpuHandle};
+
+type MapResult = Result<(), wgpu::BufferAsyncError>;
+
+/// Copies `range` of `buffer` into a new staging buffer, submits the copy, and starts mapping
+/// the staging buffer. `callback` is called once mapping finishes, on the next device poll.
+///
+/// Empty ranges have nothing to copy, so no staging buffer is returned and `callback` is called
+/// right away.
+fn start_readback(
+    gpu: &GpuHandle,
+    buffer: &wgpu::Buffer,
+    range: Range<u64>,
+    callback: impl FnOnce(MapResult) + Send + 'static,
+) -> Result<Option<wgpu::Buffer>, Error> {
+    if range.start > range.end {
+        return Err(BufferError::InvalidRange {
+            start: range.start,
+            end: range.end,
+        }
+        .into());
+    }
+
+    if range.end > buffer.size() {
+        return Err(BufferError::ReadOutOfBounds {
+            start: range.start,
+            end: range.end,
+            size: buffer.size(),
+        }
+        .into());
+    }
+
+    let size = range.end - range.start;
+
+    if size == 0 {
+        callback(Ok(()));
+        return Ok(None);
+    }
+
+    if !range.start.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+        || !size.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+    {
+        return Err(BufferError::Misaligned {
+            offset: range.start,
+            size,
+        }
+        .into());
+    }
+
+    let staging = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Staging Buffer"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = gpu.device.create_command_encoder(&Default::default());
+    encoder.copy_buffer_to_buffer(buffer, range.start, &staging, 0, size);
+    gpu.queue.submit(std::iter::once(encoder.finish()));
+
+    staging.slice(..).map_async(wgpu::MapMode::Read, callback);
+
+    Ok(Some(staging))
+}
+
+/// Copies the contents out of a mapped staging buffer and unmaps it. Empty readbacks have no
+/// staging buffer.
+fn read_mapped(staging: Option<&wgpu::Buffer>) -> Vec<u8> {
+    let Some(staging) = staging else {
+        return Vec::new();
+    };
+
+    let data = staging.slice(..).get_mapped_range().to_vec();
+    staging.unmap();
+
+    data
+}
+
+/// Reads `range` (in bytes) of the buffer back to the CPU, blocking until the GPU is done.
+/// The buffer needs `COPY_SRC` usage, and the range must be aligned to [`wgpu::COPY_BUFFER_ALIGNMENT`].
+/// Empty ranges return no data without touching the GPU.
+pub fn read_back(
+    gpu: &GpuHandle,
+    buffer: &wgpu::Buffer,
+    range: Range<u64>,
+) -> Result<Vec<u8>, Error> {
+    let (tx, rx) = mpsc::channel();
+
+    let staging = start_readback(gpu, buffer, range, move |result| {
+        // The receiver is only gone if the caller already returned with an error
+        let _ = tx.send(result);
+    })?;
+
+    gpu.device.poll(wgpu::MaintainBase::Wait)?;
+
+    rx.recv()
+        .map_err(|_| BufferError::ReadbackAborted)?
+        .map_err(BufferError::MapFailed)?;
+
+    Ok(read_mapped(staging.as_ref()))
+}
+
+/// Like [`read_back`], but casts the data to `T`s. The range is given in elements.
+pub fn read_back_typed<T: bytemuck::Pod>(
+    gpu: &GpuHandle,
+    buffer: &wgpu::Buffer,
+    range: Range<u64>,
+) -> Result<Vec<T>, Error> {
+    let element_size = size_of::<T>() as u64;
+    let bytes = read_back(
+        gpu,
+        buffer,
+        range.start * element_size..range.end * element_size,
+    )?;
+
+    // The byte vector isn't necessarily aligned for T, so copy instead of casting in place
+    Ok(bytemuck::pod_collect_to_vec(&bytes))
+}
+
+/// Like [`read_back`], but returns a future instead of blocking.
+///
+/// The future only makes progress when the device is polled, so something else
+/// (e.g. the render loop, or a separate thread) needs to call [`wgpu::Device::poll`].
+pub fn read_back_async(
+    gpu: &GpuHandle,
+    buffer: &wgpu::Buffer,
+    range: Range<u64>,
+) -> Result<ReadbackFuture, Error> {
+    let state = Arc::new(Mutex::new(FutureState::default()));
+    let callback_state = state.clone();
+
+    let staging = start_readback(gpu, buffer, range, move |result| {
+        let mut state = callback_state.lock().unwrap();
+        state.result = Some(result);
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    })?;
+
+    Ok(ReadbackFuture { staging, state })
+}
+
+#[derive(Default)]
+struct FutureState {
+    result: Option<MapResult>,
+    waker: Option<Waker>,
+}
+
+/// Future returned by [`read_back_async`].
+pub struct ReadbackFuture {
+    staging: Option<wgpu::Buffer>,
+    state: Arc<Mutex<FutureState>>,
+}
+
+impl Future for ReadbackFuture {
+    type Output = Result<Vec<u8>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.result.take() {
+            Some(Ok(())) => Poll::Ready(Ok(read_mapped(self.staging.as_ref()))),
+            Some(Err(e)) => Poll::Ready(Err(BufferError::MapFailed(e).into())),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A readback that is checked for completion without blocking, e.g. once per frame.
+pub struct ReadbackRequest {
+    staging: Option<wgpu::Buffer>,
+    receiver: mpsc::Receiver<MapResult>,
+    finished: bool,
+}
+
+impl ReadbackRequest {
+    /// Starts reading `range` (in bytes) of the buffer, with the same requirements as [`read_back`].
+    pub fn new(gpu: &GpuHandle, buffer: &wgpu::Buffer, range: Range<u64>) -> Result<Self, Error> {
+        let (tx, rx) = mpsc::channel();
+
+        let staging = start_readback(gpu, buffer, range, move |result| {
+            // The receiver is only gone if the request was dropped
+            let _ = tx.send(result);
+        })?;
+
+        Ok(Self {
+            staging,
+            receiver: rx,
+            finished: false,
+        })
+    }
+
+    /// Polls the device without blocking, and returns the data if the readback is done.
+    /// The data is only returned once; polling again afterwards is an error.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Result<Option<Vec<u8>>, Error> {
+        if self.finished {
+            return Err(BufferError::ReadbackAborted.into());
+        }
+
+        device.poll(wgpu::MaintainBase::Poll)?;
+
+        match self.receiver.try_recv() {
+            Ok(result) => {
+                result.map_err(BufferError::MapFailed)?;
+                self.finished = true;
+
+                Ok(Some(read_mapped(self.staging.as_ref())))
+            }
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(BufferError::ReadbackAborted.into()),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
@@ -0,0 +1,201 @@
+use std::sync::mpsc;
+
+use crate::{BufferError, Error};
+
+struct Chunk {
+    buffer: wgpu::Buffer,
+    /// Offset of the next free byte, only meaningful while the chunk is mapped.
+    offset: u64,
+}
+
+impl Chunk {
+    fn remaining(&self) -> u64 {
+        self.buffer.size() - self.offset
+    }
+}
+
+/// Upload statistics for a single frame, as reported by [`UploadRing::stats`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UploadStats {
+    pub bytes_uploaded: u64,
+    pub writes: usize,
+    /// Number of chunks that had to be created because none were free.
+    pub chunks_created: usize,
+}
+
+/// Streams uploads through persistently mapped staging chunks, recording the copies into an
+/// encoder instead of allocating through [`wgpu::Queue::write_buffer`] every time.
+///
+/// Each frame:
+/// 1. call [`UploadRing::write`] for every upload,
+/// 2. call [`UploadRing::finish`] before submitting the encoder,
+/// 3. call [`UploadRing::recall`] after submitting.
+///
+/// Chunks are recycled once the GPU is done with the submission, which is only noticed when the
+/// device is polled.
+pub struct UploadRing {
+    chunk_size: u64,
+
+    /// Mapped chunks being written this frame.
+    active: Vec<Chunk>,
+    /// Unmapped chunks used by the current submission.
+    closed: Vec<Chunk>,
+    /// Mapped chunks ready for reuse.
+    free: Vec<Chunk>,
+
+    /// Chunks whose submission finished, waiting to be mapped again.
+    done_sender: mpsc::Sender<Chunk>,
+    done_receiver: mpsc::Receiver<Chunk>,
+    /// Chunks that finished mapping.
+    mapped_sender: mpsc::Sender<Chunk>,
+    mapped_receiver: mpsc::Receiver<Chunk>,
+
+    current: UploadStats,
+    last: UploadStats,
+}
+
+impl UploadRing {
+    /// Creates an upload ring with chunks of `chunk_size` bytes. Larger writes get their own chunk.
+    pub fn new(chunk_size: u64) -> Self {
+        let (done_sender, done_receiver) = mpsc::channel();
+        let (mapped_sender, mapped_receiver) = mpsc::channel();
+
+        Self {
+            chunk_size,
+            active: Vec::new(),
+            closed: Vec::new(),
+            free: Vec::new(),
+            done_sender,
+            done_receiver,
+            mapped_sender,
+            mapped_receiver,
+            current: UploadStats::default(),
+            last: UploadStats::default(),
+        }
+    }
+
+    /// Copies `data` to `target` at `offset` bytes, through a staging chunk. The copy is recorded
+    /// into `encoder`. The offset and size must be aligned to [`wgpu::COPY_BUFFER_ALIGNMENT`].
+    pub fn write(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Buffer,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let size = data.len() as u64;
+
+        if size == 0 {
+            return Ok(());
+        }
+
+        if !offset.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+            || !size.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+        {
+            return Err(BufferError::Misaligned { offset, size }.into());
+        }
+
+        self.receive_chunks();
+
+        let index = match self.active.iter().position(|c| c.remaining() >= size) {
+            Some(index) => index,
+            None => {
+                let chunk = match self.free.iter().position(|c| c.remaining() >= size) {
+                    Some(index) => self.free.swap_remove(index),
+                    None => self.create_chunk(device, size),
+                };
+
+                self.active.push(chunk);
+                self.active.len() - 1
+            }
+        };
+
+        let chunk = &mut self.active[index];
+
+        chunk
+            .buffer
+            .slice(chunk.offset..chunk.offset + size)
+            .get_mapped_range_mut()
+            .copy_from_slice(data);
+
+        encoder.copy_buffer_to_buffer(&chunk.buffer, chunk.offset, target, offset, size);
+
+        // Mapped ranges must start at a multiple of the map alignment
+        chunk.offset = (chunk.offset + size)
+            .next_multiple_of(wgpu::MAP_ALIGNMENT)
+            .min(chunk.buffer.size());
+
+        self.current.bytes_uploaded += size;
+        self.current.writes += 1;
+
+        Ok(())
+    }
+
+    fn create_chunk(&mut self, device: &wgpu::Device, size: u64) -> Chunk {
+        self.current.chunks_created += 1;
+
+        Chunk {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Upload Ring Chunk"),
+                size: size.max(self.chunk_size),
+                usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            }),
+            offset: 0,
+        }
+    }
+
+    /// Maps chunks whose submission finished, and collects chunks that finished mapping.
+    fn receive_chunks(&mut self) {
+        while let Ok(mut chunk) = self.done_receiver.try_recv() {
+            let sender = self.mapped_sender.clone();
+            let buffer = chunk.buffer.clone();
+
+            buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Write, move |result| match result {
+                    Ok(()) => {
+                        chunk.offset = 0;
+
+                        // The receiver is only gone if the ring was dropped
+                        let _ = sender.send(chunk);
+                    }
+                    Err(e) => log::error!("Failed to map upload ring chunk: {}", e),
+                });
+        }
+
+        self.free.extend(self.mapped_receiver.try_iter());
+    }
+
+    /// Unmaps the chunks written this frame. Must be called before submitting the encoder
+    /// passed to [`UploadRing::write`].
+    pub fn finish(&mut self) {
+        for chunk in self.active.drain(..) {
+            chunk.buffer.unmap();
+            self.closed.push(chunk);
+        }
+    }
+
+    /// Schedules the chunks used by the last submission for reuse once the GPU is done with it,
+    /// and ends the frame for [`UploadRing::stats`]. Must be called after submitting.
+    pub fn recall(&mut self, queue: &wgpu::Queue) {
+        let closed = std::mem::take(&mut self.closed);
+        let sender = self.done_sender.clone();
+
+        queue.on_submitted_work_done(move || {
+            for chunk in closed {
+                // The receiver is only gone if the ring was dropped
+                let _ = sender.send(chunk);
+            }
+        });
+
+        self.last = std::mem::take(&mut self.current);
+        self.receive_chunks();
+    }
+
+    /// Statistics for the last frame, i.e. between the last two calls to [`UploadRing::recall`].
+    pub fn stats(&self) -> UploadStats {
+        self.last
+    }
+}
@@ -0,0 +1,169 @@
+use wgpu::util::DeviceExt;
+
+/// A vertex type whose layout can be described to a render pipeline. Usually implemented with
+/// `#[derive(Vertex)]`, available with the `derive` feature, which assigns shader locations to
+/// fields in order and takes the formats from [`VertexAttributeType`].
+pub trait Vertex: bytemuck::Pod {
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+
+    /// Layout for a buffer stepped per vertex.
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Self>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+
+    /// Layout for a buffer stepped per instance.
+    fn instance_layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            step_mode: wgpu::VertexStepMode::Instance,
+            ..Self::layout()
+        }
+    }
+}
+
+/// The [`wgpu::VertexFormat`] a field type is read as by default.
+pub trait VertexAttributeType {
+    const FORMAT: wgpu::VertexFormat;
+}
+
+macro_rules! impl_attribute_type {
+    ($($ty:ty => $format:ident),* $(,)?) => {
+        $(
+            impl VertexAttributeType for $ty {
+                const FORMAT: wgpu::VertexFormat = wgpu::VertexFormat::$format;
+            }
+        )*
+    };
+}
+
+impl_attribute_type!(
+    f32 => Float32,
+    [f32; 2] => Float32x2,
+    [f32; 3] => Float32x3,
+    [f32; 4] => Float32x4,
+    u32 => Uint32,
+    [u32; 2] => Uint32x2,
+    [u32; 3] => Uint32x3,
+    [u32; 4] => Uint32x4,
+    i32 => Sint32,
+    [i32; 2] => Sint32x2,
+    [i32; 3] => Sint32x3,
+    [i32; 4] => Sint32x4,
+    [u16; 2] => Uint16x2,
+    [u16; 4] => Uint16x4,
+    [i16; 2] => Sint16x2,
+    [i16; 4] => Sint16x4,
+    [u8; 2] => Uint8x2,
+    [u8; 4] => Uint8x4,
+    [i8; 2] => Sint8x2,
+    [i8; 4] => Sint8x4,
+);
+
+/// Creates a vertex buffer initialized with `vertices`.
+pub fn vertex_buffer<V: Vertex>(
+    device: &wgpu::Device,
+    label: &str,
+    vertices: &[V],
+) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(vertices),
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+/// An index buffer along with what's needed to draw with it.
+pub struct IndexBuffer {
+    pub buffer: wgpu::Buffer,
+    pub format: wgpu::IndexFormat,
+    pub len: u32,
+}
+
+impl IndexBuffer {
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(..)
+    }
+}
+
+/// Creates an index buffer, using 16-bit indices if they're all small enough. `0xFFFF` is
+/// reserved for primitive restart, so only indices below it are stored as 16-bit.
+pub fn index_buffer(device: &wgpu::Device, label: &str, indices: &[u32]) -> IndexBuffer {
+    let fits_u16 = indices.iter().all(|&i| i < u16::MAX as u32);
+
+    let short_indices: Vec<u16>;
+
+    let (contents, format): (&[u8], _) = match fits_u16 {
+        true => {
+            short_indices = indices.iter().map(|&i| i as u16).collect();
+            (
+                bytemuck::cast_slice(&short_indices),
+                wgpu::IndexFormat::Uint16,
+            )
+        }
+        false => (bytemuck::cast_slice(indices), wgpu::IndexFormat::Uint32),
+    };
+
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents,
+        usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+    });
+
+    IndexBuffer {
+        buffer,
+        format,
+        len: indices.len() as u32,
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, crate::buffer::Vertex)]
+    struct TestVertex {
+        position: [f32; 3],
+        #[vertex(format = Unorm8x4)]
+        color: [u8; 4],
+        #[vertex(location = 5)]
+        uv: [f32; 2],
+        layer: u32,
+    }
+
+    #[test]
+    fn derived_attributes() {
+        assert_eq!(
+            TestVertex::ATTRIBUTES,
+            [
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Unorm8x4,
+                    offset: 12,
+                    shader_location: 1,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x2,
+                    offset: 16,
+                    shader_location: 5,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Uint32,
+                    offset: 24,
+                    shader_location: 6,
+                },
+            ]
+        );
+
+        let layout = TestVertex::instance_layout();
+        assert_eq!(layout.array_stride, 28);
+        assert_eq!(layout.step_mode, wgpu::VertexStepMode::Instance);
+    }
+}
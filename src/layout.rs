@@ -0,0 +1,487 @@
+use std::fmt;
+
+use crate::{shader::ShaderSource, Error};
+
+#[cfg(feature = "derive")]
+pub use wgputil_derive::WgslLayout;
+
+/// Rounds `offset` up to a multiple of `align`, as in the WGSL spec's `roundUp`.
+pub const fn round_up(align: u64, offset: u64) -> u64 {
+    offset.div_ceil(align) * align
+}
+
+pub const fn max(a: u64, b: u64) -> u64 {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Size and alignment a type would have in a WGSL host-shareable buffer, following the WGSL
+/// memory layout rules for storage buffers, and the stricter rules of the uniform address space.
+/// Derive it for structs with `#[derive(WgslLayout)]`, available with the `derive` feature.
+///
+/// Note that arrays map to WGSL arrays, so `[f32; 3]` is laid out as `array<f32, 3>`. Use
+/// [`Vector`] and [`Matrix`] for fields that are vectors and matrices in WGSL.
+pub trait WgslLayout {
+    const WGSL_SIZE: u64;
+    const WGSL_ALIGN: u64;
+
+    /// Size in the uniform address space, which differs from [`WgslLayout::WGSL_SIZE`] for
+    /// structs whose members are placed further apart there.
+    const WGSL_UNIFORM_SIZE: u64 = Self::WGSL_SIZE;
+    /// Alignment in the uniform address space, where arrays and structs are aligned to 16.
+    const WGSL_UNIFORM_ALIGN: u64 = Self::WGSL_ALIGN;
+    /// Space taken up as a struct member in the uniform address space, where members of struct
+    /// type are padded to a multiple of 16.
+    const WGSL_UNIFORM_MEMBER_SIZE: u64 = Self::WGSL_UNIFORM_SIZE;
+    /// Whether the type can be used in the uniform address space, where array element strides
+    /// must be multiples of 16.
+    const WGSL_UNIFORM_COMPATIBLE: bool = true;
+
+    /// For structs, the layout of each member in declaration order. Empty for other types.
+    fn members() -> Vec<MemberLayout> {
+        Vec::new()
+    }
+}
+
+macro_rules! impl_scalar {
+    ($($ty:ty),*) => {
+        $(
+            impl WgslLayout for $ty {
+                const WGSL_SIZE: u64 = 4;
+                const WGSL_ALIGN: u64 = 4;
+            }
+        )*
+    };
+}
+
+impl_scalar!(f32, i32, u32);
+
+impl<T: WgslLayout, const N: usize> WgslLayout for [T; N] {
+    const WGSL_SIZE: u64 = N as u64 * round_up(T::WGSL_ALIGN, T::WGSL_SIZE);
+    const WGSL_ALIGN: u64 = T::WGSL_ALIGN;
+
+    const WGSL_UNIFORM_SIZE: u64 = N as u64 * round_up(T::WGSL_ALIGN, T::WGSL_UNIFORM_SIZE);
+    const WGSL_UNIFORM_ALIGN: u64 = round_up(16, T::WGSL_UNIFORM_ALIGN);
+    const WGSL_UNIFORM_COMPATIBLE: bool = T::WGSL_UNIFORM_COMPATIBLE
+        && round_up(T::WGSL_ALIGN, T::WGSL_UNIFORM_SIZE).is_multiple_of(16);
+}
+
+/// A WGSL `vecN<T>` of scalars, stored as an array. Vectors of 3 are aligned like vectors of 4
+/// in WGSL, but not in Rust, so their padding has to be added by hand.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vector<T, const N: usize>(pub [T; N]);
+
+pub type Vec2<T> = Vector<T, 2>;
+pub type Vec3<T> = Vector<T, 3>;
+pub type Vec4<T> = Vector<T, 4>;
+
+// Safety: transparent wrapper around an array of Pod values
+unsafe impl<T: bytemuck::Zeroable, const N: usize> bytemuck::Zeroable for Vector<T, N> {}
+unsafe impl<T: bytemuck::Pod, const N: usize> bytemuck::Pod for Vector<T, N> {}
+
+impl<T, const N: usize> From<[T; N]> for Vector<T, N> {
+    fn from(value: [T; N]) -> Self {
+        Self(value)
+    }
+}
+
+/// Alignment of a WGSL vector with `n` components of `scalar_size` bytes each.
+const fn vector_align(n: usize, scalar_size: u64) -> u64 {
+    assert!(n >= 2 && n <= 4, "WGSL vectors have 2 to 4 components");
+
+    match n {
+        3 => 4 * scalar_size,
+        n => n as u64 * scalar_size,
+    }
+}
+
+impl<T: WgslLayout, const N: usize> WgslLayout for Vector<T, N> {
+    const WGSL_SIZE: u64 = N as u64 * T::WGSL_SIZE;
+    const WGSL_ALIGN: u64 = vector_align(N, T::WGSL_SIZE);
+}
+
+/// A WGSL `matCxR<f32>` with `C` columns of `R` rows, stored as an array of columns. Columns of
+/// 3 rows are aligned like columns of 4 in WGSL, but not in Rust, so their padding has to be
+/// added by hand.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix<const C: usize, const R: usize>(pub [[f32; R]; C]);
+
+pub type Mat2x2 = Matrix<2, 2>;
+pub type Mat3x3 = Matrix<3, 3>;
+pub type Mat4x4 = Matrix<4, 4>;
+
+// Safety: transparent wrapper around an array of f32s
+unsafe impl<const C: usize, const R: usize> bytemuck::Zeroable for Matrix<C, R> {}
+unsafe impl<const C: usize, const R: usize> bytemuck::Pod for Matrix<C, R> {}
+
+impl<const C: usize, const R: usize> From<[[f32; R]; C]> for Matrix<C, R> {
+    fn from(value: [[f32; R]; C]) -> Self {
+        Self(value)
+    }
+}
+
+impl<const C: usize, const R: usize> WgslLayout for Matrix<C, R> {
+    const WGSL_SIZE: u64 = {
+        assert!(C >= 2 && C <= 4, "WGSL matrices have 2 to 4 columns");
+        C as u64 * round_up(vector_align(R, 4), R as u64 * 4)
+    };
+    const WGSL_ALIGN: u64 = vector_align(R, 4);
+}
+
+/// Layout of a single struct member, on the Rust side and as computed from the WGSL rules.
+#[derive(Clone, Debug)]
+pub struct MemberLayout {
+    pub name: &'static str,
+    pub size: u64,
+
+    pub rust_offset: u64,
+    pub wgsl_offset: u64,
+    /// Offset following the uniform address space rules.
+    pub wgsl_uniform_offset: u64,
+    /// See [`WgslLayout::WGSL_UNIFORM_COMPATIBLE`].
+    pub uniform_compatible: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayoutMismatch {
+    MemberCount {
+        rust: usize,
+        wgsl: usize,
+    },
+    Offset {
+        member: &'static str,
+        rust: u64,
+        wgsl: u64,
+    },
+    /// `member` is `None` for the size of the whole struct.
+    Size {
+        member: Option<&'static str>,
+        rust: u64,
+        wgsl: u64,
+    },
+    /// The member is or contains an array that can't be used in a uniform buffer.
+    UniformArrayStride {
+        member: &'static str,
+    },
+}
+
+impl fmt::Display for LayoutMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutMismatch::MemberCount { rust, wgsl } => {
+                write!(f, "Rust struct has {rust} members, WGSL struct has {wgsl}")
+            }
+            LayoutMismatch::Offset { member, rust, wgsl } => write!(
+                f,
+                "member {member} is at offset {rust} in Rust, but {wgsl} in WGSL"
+            ),
+            LayoutMismatch::Size { member, rust, wgsl } => match member {
+                Some(member) => write!(
+                    f,
+                    "member {member} is {rust} bytes in Rust, but {wgsl} in WGSL"
+                ),
+                None => write!(f, "struct is {rust} bytes in Rust, but {wgsl} in WGSL"),
+            },
+            LayoutMismatch::UniformArrayStride { member } => write!(
+                f,
+                "member {member} has an array stride that isn't a multiple of 16, which uniform \
+                 buffers don't allow"
+            ),
+        }
+    }
+}
+
+/// Checks the Rust layout of `T` against the layout the WGSL rules give for the same member
+/// types in a storage buffer. This catches missing padding without needing a shader.
+pub fn check_host_layout<T: WgslLayout>() -> Vec<LayoutMismatch> {
+    host_layout_mismatches::<T>(T::WGSL_SIZE, |m| m.wgsl_offset)
+}
+
+/// Like [`check_host_layout`], but for a uniform buffer, where nested arrays and structs are
+/// aligned to 16 bytes and nested structs are padded to a multiple of 16. Members with arrays
+/// whose element stride isn't a multiple of 16, like `[f32; 4]`, can't be used in a uniform
+/// buffer at all and are reported as [`LayoutMismatch::UniformArrayStride`].
+pub fn check_host_layout_uniform<T: WgslLayout>() -> Vec<LayoutMismatch> {
+    let mut mismatches: Vec<_> = T::members()
+        .into_iter()
+        .filter(|m| !m.uniform_compatible)
+        .map(|m| LayoutMismatch::UniformArrayStride { member: m.name })
+        .collect();
+
+    mismatches.extend(host_layout_mismatches::<T>(T::WGSL_UNIFORM_SIZE, |m| {
+        m.wgsl_uniform_offset
+    }));
+    mismatches
+}
+
+fn host_layout_mismatches<T: WgslLayout>(
+    wgsl_size: u64,
+    wgsl_offset: impl Fn(&MemberLayout) -> u64,
+) -> Vec<LayoutMismatch> {
+    let mut mismatches: Vec<_> = T::members()
+        .into_iter()
+        .filter(|m| m.rust_offset != wgsl_offset(m))
+        .map(|m| LayoutMismatch::Offset {
+            member: m.name,
+            rust: m.rust_offset,
+            wgsl: wgsl_offset(&m),
+        })
+        .collect();
+
+    if size_of::<T>() as u64 != wgsl_size {
+        mismatches.push(LayoutMismatch::Size {
+            member: None,
+            rust: size_of::<T>() as u64,
+            wgsl: wgsl_size,
+        });
+    }
+
+    mismatches
+}
+
+/// Checks the Rust layout of `T` against the struct named `struct_name` in the shader, using
+/// naga reflection. Members are matched by position, since names may differ between languages.
+pub fn check_against<T: WgslLayout>(
+    source: &ShaderSource,
+    struct_name: &str,
+) -> Result<Vec<LayoutMismatch>, Error> {
+    let module = source.reflect()?;
+
+    let reflection_error = |message: String| Error::ShaderReflection {
+        name: source.name().to_owned(),
+        message,
+    };
+
+    let (wgsl_members, span) = module
+        .types
+        .iter()
+        .find_map(|(_, ty)| match &ty.inner {
+            naga::TypeInner::Struct { members, span }
+                if ty.name.as_deref() == Some(struct_name) =>
+            {
+                Some((members, *span))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| reflection_error(format!("struct {struct_name} not found")))?;
+
+    let mut layouter = naga::proc::Layouter::default();
+    layouter
+        .update(module.to_ctx())
+        .map_err(|e| reflection_error(e.to_string()))?;
+
+    let rust_members = T::members();
+    let mut mismatches = Vec::new();
+
+    if rust_members.len() != wgsl_members.len() {
+        mismatches.push(LayoutMismatch::MemberCount {
+            rust: rust_members.len(),
+            wgsl: wgsl_members.len(),
+        });
+    }
+
+    for (rust, wgsl) in rust_members.iter().zip(wgsl_members) {
+        let wgsl_offset = wgsl.offset as u64;
+        let wgsl_size = layouter[wgsl.ty].size as u64;
+
+        if rust.rust_offset != wgsl_offset {
+            mismatches.push(LayoutMismatch::Offset {
+                member: rust.name,
+                rust: rust.rust_offset,
+                wgsl: wgsl_offset,
+            });
+        }
+
+        if rust.size != wgsl_size {
+            mismatches.push(LayoutMismatch::Size {
+                member: Some(rust.name),
+                rust: rust.size,
+                wgsl: wgsl_size,
+            });
+        }
+    }
+
+    if size_of::<T>() as u64 != span as u64 {
+        mismatches.push(LayoutMismatch::Size {
+            member: None,
+            rust: size_of::<T>() as u64,
+            wgsl: span as u64,
+        });
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vector_and_matrix_layouts() {
+        assert_eq!((Vec2::<f32>::WGSL_SIZE, Vec2::<f32>::WGSL_ALIGN), (8, 8));
+        assert_eq!((Vec3::<f32>::WGSL_SIZE, Vec3::<f32>::WGSL_ALIGN), (12, 16));
+        assert_eq!((Vec4::<u32>::WGSL_SIZE, Vec4::<u32>::WGSL_ALIGN), (16, 16));
+
+        assert_eq!((Mat2x2::WGSL_SIZE, Mat2x2::WGSL_ALIGN), (16, 8));
+        assert_eq!((Mat3x3::WGSL_SIZE, Mat3x3::WGSL_ALIGN), (48, 16));
+        assert_eq!((Mat4x4::WGSL_SIZE, Mat4x4::WGSL_ALIGN), (64, 16));
+        assert_eq!(
+            (Matrix::<4, 2>::WGSL_SIZE, Matrix::<4, 2>::WGSL_ALIGN),
+            (32, 8)
+        );
+    }
+
+    #[test]
+    fn uniform_array_stride() {
+        fn uniform<T: WgslLayout>() -> (u64, u64, bool) {
+            (
+                T::WGSL_UNIFORM_SIZE,
+                T::WGSL_UNIFORM_ALIGN,
+                T::WGSL_UNIFORM_COMPATIBLE,
+            )
+        }
+
+        assert_eq!(uniform::<[f32; 4]>(), (16, 16, false));
+        assert_eq!(uniform::<[Vec2<f32>; 2]>(), (16, 16, false));
+        assert_eq!(uniform::<[Vec3<f32>; 2]>(), (32, 16, true));
+        assert_eq!(uniform::<[Vec4<f32>; 2]>(), (32, 16, true));
+        assert_eq!(uniform::<[[Vec4<f32>; 2]; 2]>(), (64, 16, true));
+    }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use super::*;
+
+        #[repr(C)]
+        #[derive(WgslLayout)]
+        struct Unpadded {
+            a: Vec3<f32>,
+            b: f32,
+            c: u32,
+        }
+
+        #[repr(C)]
+        #[derive(WgslLayout)]
+        struct Padded {
+            a: Vec3<f32>,
+            b: f32,
+            c: u32,
+            _pad: [u32; 3],
+        }
+
+        #[repr(C)]
+        #[derive(WgslLayout)]
+        struct Scalars {
+            a: [f32; 2],
+            b: u32,
+        }
+
+        #[repr(C)]
+        #[derive(WgslLayout)]
+        struct One {
+            a: f32,
+        }
+
+        #[repr(C)]
+        #[derive(WgslLayout)]
+        struct Nested {
+            inner: One,
+            b: f32,
+        }
+
+        fn shader_check<T: WgslLayout>(source: &str) -> Vec<LayoutMismatch> {
+            let source = ShaderSource::from_wgsl_str("test", source);
+            check_against::<T>(&source, "S").unwrap()
+        }
+
+        fn validates(source: &str) -> bool {
+            ShaderSource::from_wgsl_str("test", source)
+                .validate(naga::valid::Capabilities::default())
+                .is_ok()
+        }
+
+        #[test]
+        fn vec3_alignment_is_caught() {
+            assert_eq!(Unpadded::WGSL_SIZE, 32);
+            assert_eq!(
+                check_host_layout::<Unpadded>(),
+                [LayoutMismatch::Size {
+                    member: None,
+                    rust: 20,
+                    wgsl: 32
+                }]
+            );
+
+            assert!(check_host_layout::<Padded>().is_empty());
+            assert!(shader_check::<Padded>(
+                "struct S { a: vec3<f32>, b: f32, c: u32, pad: array<u32, 3> }
+                     @group(0) @binding(0) var<storage> s: S;"
+            )
+            .is_empty());
+        }
+
+        #[test]
+        fn uniform_top_level_struct() {
+            assert_eq!(One::WGSL_UNIFORM_SIZE, 4);
+            assert!(check_host_layout_uniform::<One>().is_empty());
+
+            let source = "struct S { a: f32 }
+                @group(0) @binding(0) var<uniform> s: S;";
+            assert!(validates(source));
+            assert!(shader_check::<One>(source).is_empty());
+        }
+
+        #[test]
+        fn uniform_nested_struct() {
+            assert!(check_host_layout::<Nested>().is_empty());
+            assert_eq!(Nested::WGSL_UNIFORM_SIZE, 20);
+
+            assert_eq!(
+                check_host_layout_uniform::<Nested>(),
+                [
+                    LayoutMismatch::Offset {
+                        member: "b",
+                        rust: 4,
+                        wgsl: 16
+                    },
+                    LayoutMismatch::Size {
+                        member: None,
+                        rust: 8,
+                        wgsl: 20
+                    },
+                ]
+            );
+
+            // The nested struct has to be padded to 16 bytes in uniform buffers
+            assert!(!validates(
+                "struct One { a: f32 }
+                 struct S { inner: One, b: f32 }
+                 @group(0) @binding(0) var<uniform> s: S;"
+            ));
+            assert!(validates(
+                "struct One { a: f32 }
+                 struct S { @size(16) inner: One, b: f32 }
+                 @group(0) @binding(0) var<uniform> s: S;"
+            ));
+        }
+
+        #[test]
+        fn uniform_array_stride_is_reported() {
+            assert!(check_host_layout::<Scalars>().is_empty());
+            assert_eq!(
+                check_host_layout_uniform::<Scalars>(),
+                [LayoutMismatch::UniformArrayStride { member: "a" }]
+            );
+
+            assert!(!validates(
+                "struct S { a: array<f32, 2>, b: u32 }
+                 @group(0) @binding(0) var<uniform> s: S;"
+            ));
+        }
+    }
+}
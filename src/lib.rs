@@ -3,27 +3,71 @@ use std::sync::Arc;
 use thiserror::Error;
 use winit::window::Window;
 
+// Lets derive macros, which refer to `::wgputil`, be used inside this crate
+extern crate self as wgputil;
+
 pub mod binding;
 pub mod buffer;
+pub mod layout;
+pub mod pipeline;
 pub mod profile;
 pub mod shader;
 pub mod texture;
 
 pub(crate) mod util;
 
+/// Dependencies used by the derive macros, so crates using them don't need their own (matching)
+/// `wgpu` dependency.
+#[doc(hidden)]
+pub mod __private {
+    pub use wgpu;
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Texture error: {0}")]
     Texture(#[from] TextureError),
 
+    #[error("Buffer error: {0}")]
+    Buffer(#[from] BufferError),
+
+    #[error("Binding error: {0}")]
+    Binding(#[from] BindingError),
+
+    #[error("Pipeline error: {0}")]
+    Pipeline(#[from] PipelineError),
+
+    #[error("Missing required features {0:?}")]
+    MissingFeatures(wgpu::Features),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    #[cfg(feature = "hot-reload")]
+    #[error("File watcher error: {0}")]
+    Watch(#[from] notify::Error),
+
     #[error("wgpu error: {0}")]
     Wgpu(#[from] wgpu::Error),
 
     #[error("wgpu poll error: {0}")]
     WgpuPoll(#[from] wgpu::PollError),
+
+    #[error("Shader error: {0}")]
+    Shader(#[from] ShaderError),
+
+    #[error("Shader compile error in {name}:\n{rendered}")]
+    ShaderCompile {
+        name: String,
+        path: std::path::PathBuf,
+        message: String,
+        spans: Vec<shader::SourceSpan>,
+        /// The message with the offending source lines, rendered like a compiler diagnostic.
+        rendered: String,
+    },
+
+    #[error("Shader reflection error in {name}: {message}")]
+    ShaderReflection { name: String, message: String },
 }
 
 #[derive(Error, Debug)]
@@ -32,6 +76,80 @@ pub enum TextureError {
     InvalidFormat(wgpu::TextureFormat),
 }
 
+#[derive(Error, Debug)]
+pub enum ShaderError {
+    #[error("{}:{line}: {message}", path.display())]
+    Preprocess {
+        path: std::path::PathBuf,
+        line: u32,
+        message: String,
+    },
+
+    #[error("Invalid Spir-V in {name}: {message}")]
+    InvalidSpirv { name: String, message: String },
+}
+
+#[derive(Error, Debug)]
+pub enum BufferError {
+    #[error("Write of {len} elements at index {index} is out of bounds for a buffer of {capacity} elements")]
+    OutOfBounds {
+        index: usize,
+        len: usize,
+        capacity: usize,
+    },
+
+    #[error("Read of bytes {start}..{end} is out of bounds for a buffer of {size} bytes")]
+    ReadOutOfBounds { start: u64, end: u64, size: u64 },
+
+    #[error("Invalid byte range {start}..{end}, the start is after the end")]
+    InvalidRange { start: u64, end: u64 },
+
+    #[error(
+        "Copy of {size} bytes at offset {offset} is not aligned to {}",
+        wgpu::COPY_BUFFER_ALIGNMENT
+    )]
+    Misaligned { offset: u64, size: u64 },
+
+    #[error("Buffer write failed validation")]
+    WriteFailed,
+
+    #[error("Buffer map failed: {0}")]
+    MapFailed(#[from] wgpu::BufferAsyncError),
+
+    #[error("Readback finished or was aborted before the buffer was mapped")]
+    ReadbackAborted,
+}
+
+#[derive(Error, Debug)]
+pub enum BindingError {
+    #[error("Binding array count {count} must be between 1 and the device limit of {limit}")]
+    InvalidArrayCount { count: u32, limit: u32 },
+
+    #[error("Bindless table {0} is full")]
+    TableFull(String),
+}
+
+#[derive(Error, Debug)]
+pub enum PipelineError {
+    #[error("No bind group layout given for group {0}")]
+    MissingBindGroup(u32),
+
+    #[error("Bind group layout given more than once for group {0}")]
+    DuplicateBindGroup(u32),
+
+    #[error("{count} bind groups exceed the device limit of {limit}")]
+    TooManyBindGroups { count: u32, limit: u32 },
+
+    #[error("Push constant size {0} is not a multiple of 4")]
+    MisalignedPushConstants(u32),
+
+    #[error("{size} bytes of push constants exceed the device limit of {limit}")]
+    PushConstantsTooLarge { size: u32, limit: u32 },
+
+    #[error("Shader stages {0:?} are used by more than one push constant range")]
+    OverlappingPushConstantStages(wgpu::ShaderStages),
+}
+
 #[derive(Clone)]
 pub struct GpuHandle {
     pub instance: wgpu::Instance,
@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+use crate::{Error, PipelineError};
+
+/// Builds a [`wgpu::PipelineLayout`] from bind group layouts given by group index, and push
+/// constant ranges sized after `Pod` types. Everything is validated against the device when
+/// calling [`PipelineLayoutBuilder::build`].
+pub struct PipelineLayoutBuilder<'a> {
+    label: &'a str,
+
+    bind_group_layouts: BTreeMap<u32, &'a wgpu::BindGroupLayout>,
+    duplicate_group: Option<u32>,
+
+    push_constant_ranges: Vec<wgpu::PushConstantRange>,
+}
+
+impl<'a> PipelineLayoutBuilder<'a> {
+    pub fn new(label: &'a str) -> Self {
+        Self {
+            label,
+            bind_group_layouts: BTreeMap::new(),
+            duplicate_group: None,
+            push_constant_ranges: Vec::new(),
+        }
+    }
+
+    /// Sets the layout for `@group(index)`. Every group up to the highest index must be given.
+    pub fn bind_group(mut self, index: u32, layout: &'a wgpu::BindGroupLayout) -> Self {
+        if self.bind_group_layouts.insert(index, layout).is_some() {
+            self.duplicate_group.get_or_insert(index);
+        }
+
+        self
+    }
+
+    /// Adds a push constant range the size of `T`, placed right after the previously added range.
+    /// Each shader stage may only be used in one range.
+    pub fn push_constants<T: bytemuck::Pod>(self, stages: wgpu::ShaderStages) -> Self {
+        self.push_constants_sized(stages, size_of::<T>() as u32)
+    }
+
+    /// Adds a push constant range of `size` bytes, placed right after the previously added range.
+    pub fn push_constants_sized(mut self, stages: wgpu::ShaderStages, size: u32) -> Self {
+        let start = self.push_constant_size();
+
+        self.push_constant_ranges.push(wgpu::PushConstantRange {
+            stages,
+            range: start..start + size,
+        });
+
+        self
+    }
+
+    fn push_constant_size(&self) -> u32 {
+        self.push_constant_ranges
+            .last()
+            .map(|r| r.range.end)
+            .unwrap_or(0)
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> Result<wgpu::PipelineLayout, Error> {
+        if let Some(index) = self.duplicate_group {
+            return Err(PipelineError::DuplicateBindGroup(index).into());
+        }
+
+        // Keys are sorted, so any gap shows up as a key that doesn't match its position
+        if let Some(gap) = (0..)
+            .zip(self.bind_group_layouts.keys())
+            .find(|(expected, &index)| *expected != index)
+            .map(|(expected, _)| expected)
+        {
+            return Err(PipelineError::MissingBindGroup(gap).into());
+        }
+
+        let limits = device.limits();
+
+        let count = self.bind_group_layouts.len() as u32;
+        if count > limits.max_bind_groups {
+            return Err(PipelineError::TooManyBindGroups {
+                count,
+                limit: limits.max_bind_groups,
+            }
+            .into());
+        }
+
+        if !self.push_constant_ranges.is_empty() {
+            if !device.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+                return Err(Error::MissingFeatures(wgpu::Features::PUSH_CONSTANTS));
+            }
+
+            if let Some(range) = self
+                .push_constant_ranges
+                .iter()
+                .find(|r| (r.range.end - r.range.start) % wgpu::PUSH_CONSTANT_ALIGNMENT != 0)
+            {
+                return Err(PipelineError::MisalignedPushConstants(
+                    range.range.end - range.range.start,
+                )
+                .into());
+            }
+
+            let mut used_stages = wgpu::ShaderStages::empty();
+            for range in &self.push_constant_ranges {
+                let overlap = used_stages & range.stages;
+                if !overlap.is_empty() {
+                    return Err(PipelineError::OverlappingPushConstantStages(overlap).into());
+                }
+
+                used_stages |= range.stages;
+            }
+
+            let size = self.push_constant_size();
+            if size > limits.max_push_constant_size {
+                return Err(PipelineError::PushConstantsTooLarge {
+                    size,
+                    limit: limits.max_push_constant_size,
+                }
+                .into());
+            }
+        }
+
+        let bind_group_layouts: Vec<_> = self.bind_group_layouts.into_values().collect();
+
+        Ok(
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(self.label),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &self.push_constant_ranges,
+            }),
+        )
+    }
+}
@@ -1,16 +1,12 @@
-use std::{
-    sync::{mpsc, Arc},
-    time::Duration,
-};
+use std::time::Duration;
 
-use crate::{Error, GpuHandle};
+use crate::{buffer, Error, GpuHandle};
 
 pub struct TimeQuery {
     started: bool,
 
     query_set: wgpu::QuerySet,
     resolve_buffer: wgpu::Buffer,
-    readback_buffer: Arc<wgpu::Buffer>,
 }
 
 impl TimeQuery {
@@ -28,24 +24,14 @@ impl TimeQuery {
             mapped_at_creation: false,
         });
 
-        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: 2 * 8, // 2 u64s, 8 bytes each
-            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        let readback_buffer = Arc::new(readback_buffer);
-
         Self {
             started: false,
             query_set,
             resolve_buffer,
-            readback_buffer,
         }
     }
 
-    pub fn compute_timestamp_writes(&self) -> wgpu::ComputePassTimestampWrites {
+    pub fn compute_timestamp_writes(&self) -> wgpu::ComputePassTimestampWrites<'_> {
         wgpu::ComputePassTimestampWrites {
             query_set: &self.query_set,
             beginning_of_pass_write_index: Some(0),
@@ -53,7 +39,7 @@ impl TimeQuery {
         }
     }
 
-    pub fn render_timestamp_writes(&self) -> wgpu::RenderPassTimestampWrites {
+    pub fn render_timestamp_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
         wgpu::RenderPassTimestampWrites {
             query_set: &self.query_set,
             beginning_of_pass_write_index: Some(0),
@@ -84,15 +70,6 @@ impl TimeQuery {
 
     fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
         encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
-
-        // Copy the data to a mapped buffer so it can be read on the cpu
-        encoder.copy_buffer_to_buffer(
-            &self.resolve_buffer,
-            0,
-            &self.readback_buffer,
-            0,
-            self.resolve_buffer.size(),
-        );
     }
 
     pub fn read(&self, gpu: &GpuHandle) -> Result<Duration, Error> {
@@ -103,32 +80,10 @@ impl TimeQuery {
 
         gpu.queue.submit(std::iter::once(encoder.finish()));
 
-        let (tx, rx) = mpsc::channel();
-
-        let buffer = self.readback_buffer.clone();
-
-        self.readback_buffer
-            .slice(..)
-            .map_async(wgpu::MapMode::Read, move |result| {
-                match result {
-                    Ok(()) => {
-                        let view = buffer.slice(..).get_mapped_range();
-                        let timestamps: &[u64] = bytemuck::cast_slice(&view);
-
-                        let time_start = timestamps[0];
-                        let time_end = timestamps[1];
-
-                        tx.send((time_start, time_end)).unwrap();
-                    }
-                    Err(e) => log::error!("Buffer map failed: {}", e),
-                }
-
-                buffer.unmap();
-            });
-
-        gpu.device.poll(wgpu::MaintainBase::Wait)?;
+        // the resolved timestamps are copied to a mapped buffer so they can be read on the cpu
+        let timestamps = buffer::read_back_typed::<u64>(gpu, &self.resolve_buffer, 0..2)?;
 
-        let (start, end) = rx.recv().unwrap();
+        let (start, end) = (timestamps[0], timestamps[1]);
 
         let timestamp_period = gpu.queue.get_timestamp_period() as f64;
         let nanoseconds = (end - start) as f64 * timestamp_period;
@@ -1,81 +1,338 @@
 use std::{
     borrow::Cow,
+    collections::BTreeMap,
     path::{Path, PathBuf},
 };
 
-use crate::{util, Error};
+use crate::{util, Error, ShaderError};
+
+mod diagnostic;
+mod fallback;
+mod preprocess;
+mod variants;
+#[cfg(feature = "hot-reload")]
+mod watch;
+
+use preprocess::Preprocessed;
+
+pub use diagnostic::SourceSpan;
+pub use variants::ShaderVariants;
+
+#[cfg(feature = "hot-reload")]
+pub use watch::ShaderWatcher;
 
 /// The shader backend to use.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 #[non_exhaustive]
 pub enum ShaderBackend {
     Wgsl,
+    /// SPIR-V, translated and validated by naga like WGSL.
     Spirv,
+    /// SPIR-V handed to the driver as-is, see [`ShaderSource::load_spirv_passthrough`].
+    SpirvPassthrough,
+    /// GLSL for a single shader stage, with defines for the GLSL preprocessor.
+    #[cfg(feature = "glsl")]
+    Glsl {
+        stage: naga::ShaderStage,
+        defines: BTreeMap<String, String>,
+    },
 }
 
 enum ShaderModuleDescriptor<'a> {
-    Wgsl(wgpu::ShaderModuleDescriptor<'a>),
+    /// Checked for errors with an error scope.
+    Checked(wgpu::ShaderModuleDescriptor<'a>),
     Passthrough(wgpu::ShaderModuleDescriptorPassthrough<'a>),
 }
 
+#[derive(Clone)]
 struct ShaderMetadata {
     pub name: String,
     pub path: PathBuf,
     pub backend: ShaderBackend,
+    pub defines: BTreeMap<String, String>,
+    /// Source to use instead of reading `path`, for shaders from memory.
+    pub embedded: Option<Vec<u8>>,
+    /// WGSL fallback registered with [`ShaderSource::with_fallback`].
+    pub fallback: Option<String>,
+}
+
+/// Embeds the WGSL file at `path`, relative to the calling crate's manifest directory, with
+/// [`ShaderSource::load_wgsl_embedded`]. Debug builds of the calling crate read the file from
+/// disk instead if it exists, release builds always use the embedded source.
+#[macro_export]
+macro_rules! embed_wgsl {
+    ($path:expr) => {
+        $crate::shader::ShaderSource::load_wgsl_embedded(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/", $path),
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $path)),
+            cfg!(debug_assertions),
+        )
+    };
+}
+
+/// Embeds the Spir-V file at `path` with [`ShaderSource::load_spirv_embedded`], reading it from
+/// disk instead in debug builds of the calling crate, like [`embed_wgsl!`].
+#[macro_export]
+macro_rules! embed_spirv {
+    ($path:expr) => {
+        $crate::shader::ShaderSource::load_spirv_embedded(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/", $path),
+            include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $path)),
+            cfg!(debug_assertions),
+        )
+    };
 }
 
 /// Contains data about shader source, including label, path, and backend (if not WGSL).
 /// Used for managing shaders that may fail to compile, or whose contents may need to be
-/// reloaded/reread from the source file.
+/// reloaded/reread from the source file. Shaders can also be created from memory, e.g. from
+/// `include_str!`, in which case reloading only resets them to the original source.
 pub struct ShaderSource {
     metadata: ShaderMetadata,
     source: Option<Vec<u8>>,
+
+    /// Includes resolved while loading a WGSL source, empty for other backends.
+    preprocessed: Preprocessed,
+    /// Why the source couldn't be loaded, if it's fallback for that reason.
+    load_error: Option<Error>,
+    /// Fallback matching the interface of the last source that could be parsed.
+    generated_fallback: Option<String>,
 }
 
 impl ShaderSource {
-    fn load<P: AsRef<Path>>(path: P, backend: ShaderBackend) -> Self {
+    fn load<P: AsRef<Path>>(
+        path: P,
+        backend: ShaderBackend,
+        defines: BTreeMap<String, String>,
+    ) -> Self {
         let name = util::name_from_path(&path).unwrap_or_default();
-        let path = path.as_ref().to_owned();
+        let name = labeled_name(name, &backend, &defines);
 
-        let metadata = ShaderMetadata {
+        Self::from_metadata(ShaderMetadata {
             name,
-            path,
+            path: path.as_ref().to_owned(),
+            backend,
+            defines,
+            embedded: None,
+            fallback: None,
+        })
+    }
+
+    /// Creates a source with the file name of `path` as its name, but with the given contents.
+    fn embedded<P: AsRef<Path>>(path: P, backend: ShaderBackend, embedded: &[u8]) -> Self {
+        let name = util::name_from_path(&path).unwrap_or_default();
+
+        Self::from_metadata(ShaderMetadata {
+            name,
+            path: path.as_ref().to_owned(),
             backend,
+            defines: BTreeMap::new(),
+            embedded: Some(embedded.to_vec()),
+            fallback: None,
+        })
+    }
+
+    fn from_metadata(metadata: ShaderMetadata) -> Self {
+        let mut preprocessed = Preprocessed::default();
+
+        let read = |path: &Path| match &metadata.embedded {
+            Some(embedded) => Ok(embedded.clone()),
+            None => std::fs::read(path).map_err(Error::from),
         };
 
-        fn read_shader_source<U: AsRef<Path>>(
-            path: U,
-            backend: ShaderBackend,
-        ) -> std::io::Result<Vec<u8>> {
-            match backend {
-                ShaderBackend::Wgsl => {
-                    let source = std::fs::read_to_string(&path)?;
-                    Ok(source.into_bytes())
+        let source = match &metadata.backend {
+            ShaderBackend::Wgsl => {
+                // Embedded WGSL always comes from a `&str`
+                let embedded = metadata
+                    .embedded
+                    .as_deref()
+                    .map(|e| std::str::from_utf8(e).unwrap());
+
+                preprocess::preprocess(
+                    &metadata.path,
+                    embedded,
+                    &metadata.defines,
+                    &mut preprocessed,
+                )
+                .map(|()| std::mem::take(&mut preprocessed.source).into_bytes())
+            }
+            ShaderBackend::Spirv | ShaderBackend::SpirvPassthrough => read(&metadata.path),
+            // Read as a string, so invalid UTF-8 is a load error
+            #[cfg(feature = "glsl")]
+            ShaderBackend::Glsl { .. } => std::fs::read_to_string(&metadata.path)
+                .map(String::into_bytes)
+                .map_err(Error::from),
+        };
+
+        let (source, load_error) = match source {
+            Ok(source) => (Some(source), None),
+            Err(e) => {
+                if !matches!(e, Error::Io(_)) {
+                    log::error!("Failed to preprocess shader {}: {}", metadata.name, e);
                 }
-                ShaderBackend::Spirv => Ok(std::fs::read(&path)?),
+
+                (None, Some(e))
             }
+        };
+
+        Self {
+            metadata,
+            source,
+            preprocessed,
+            load_error,
+            generated_fallback: None,
         }
+        .with_generated_fallback()
+    }
 
-        let source = read_shader_source(&metadata.path, backend).ok();
+    /// Generates a fallback from the reflected interface of the source, if it can be parsed.
+    fn with_generated_fallback(mut self) -> Self {
+        if self.is_fallback() {
+            return self;
+        }
+
+        let generated = self
+            .reflect()
+            .ok()
+            .and_then(|module| fallback::generate(&module));
 
-        Self { metadata, source }
+        self.generated_fallback = generated.filter(|source| match fallback::validate(source) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!(
+                    "Generated fallback for shader {} is invalid, using the default fallback: {}",
+                    self.metadata.name,
+                    e
+                );
+                false
+            }
+        });
+
+        self
     }
 
     /// Create a WGSL [`ShaderSource`] given a path
     pub fn load_wgsl<P: AsRef<Path>>(path: P) -> Self {
-        Self::load(path, ShaderBackend::Wgsl)
+        Self::load(path, ShaderBackend::Wgsl, BTreeMap::new())
+    }
+
+    /// Create a WGSL [`ShaderSource`] given a path and a set of defines for the preprocessor.
+    /// Use an empty value for defines only checked with `#ifdef`/`#ifndef`.
+    ///
+    /// The shader name is labeled with the defines, e.g. `lighting[SHADOWS,SAMPLES=4]`.
+    pub fn load_wgsl_with_defines<P, I, K, V>(path: P, defines: I) -> Self
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let defines = defines
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+
+        Self::load(path, ShaderBackend::Wgsl, defines)
+    }
+
+    /// Create a WGSL [`ShaderSource`] from a string, e.g. one embedded with `include_str!` or
+    /// generated at runtime. The name is also used as its path, so `#include`s are resolved
+    /// relative to the working directory.
+    pub fn from_wgsl_str(name: &str, source: &str) -> Self {
+        let mut shader = Self::embedded(name, ShaderBackend::Wgsl, source.as_bytes());
+        shader.metadata.name = name.to_owned();
+        shader
+    }
+
+    /// Create a Spir-V [`ShaderSource`] from bytes, e.g. ones embedded with `include_bytes!`.
+    pub fn from_spirv_bytes(name: &str, bytes: &[u8]) -> Self {
+        let mut shader = Self::embedded(name, ShaderBackend::Spirv, bytes);
+        shader.metadata.name = name.to_owned();
+        shader
+    }
+
+    /// Create a WGSL [`ShaderSource`] from embedded source. With `prefer_disk`, the file at
+    /// `path` is used instead if it exists, so it can be edited and reloaded during development.
+    /// [`embed_wgsl!`](crate::embed_wgsl) embeds a file and prefers the disk in debug builds of
+    /// the calling crate.
+    ///
+    /// `#include`s are resolved relative to `path` either way.
+    pub fn load_wgsl_embedded<P: AsRef<Path>>(path: P, embedded: &str, prefer_disk: bool) -> Self {
+        match prefer_disk && path.as_ref().is_file() {
+            true => Self::load_wgsl(path),
+            false => Self::embedded(path, ShaderBackend::Wgsl, embedded.as_bytes()),
+        }
+    }
+
+    /// Like [`ShaderSource::load_wgsl_embedded`], for Spir-V. See also
+    /// [`embed_spirv!`](crate::embed_spirv).
+    pub fn load_spirv_embedded<P: AsRef<Path>>(
+        path: P,
+        embedded: &[u8],
+        prefer_disk: bool,
+    ) -> Self {
+        match prefer_disk && path.as_ref().is_file() {
+            true => Self::load_spirv(path),
+            false => Self::embedded(path, ShaderBackend::Spirv, embedded),
+        }
     }
 
     /// Create a Spir-V [`ShaderSource`] given a path
     pub fn load_spirv<P: AsRef<Path>>(path: P) -> Self {
-        Self::load(path, ShaderBackend::Spirv)
+        Self::load(path, ShaderBackend::Spirv, BTreeMap::new())
+    }
+
+    /// Create a Spir-V [`ShaderSource`] given a path, which is passed to the driver without
+    /// translation or validation. Creating the module requires
+    /// [`wgpu::Features::SPIRV_SHADER_PASSTHROUGH`].
+    ///
+    /// # Safety
+    ///
+    /// The SPIR-V must be valid for the device, invalid shaders may crash the driver.
+    pub unsafe fn load_spirv_passthrough<P: AsRef<Path>>(path: P) -> Self {
+        Self::load(path, ShaderBackend::SpirvPassthrough, BTreeMap::new())
+    }
+
+    /// Create a GLSL [`ShaderSource`] for one shader stage given a path, and defines for the
+    /// GLSL preprocessor. GLSL doesn't go through the WGSL preprocessor, so `#include` isn't
+    /// supported.
+    ///
+    /// The shader name is labeled with the defines, like WGSL variants.
+    #[cfg(feature = "glsl")]
+    pub fn load_glsl<P, I, K, V>(path: P, stage: naga::ShaderStage, defines: I) -> Self
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let defines = defines
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+
+        Self::load(
+            path,
+            ShaderBackend::Glsl { stage, defines },
+            BTreeMap::new(),
+        )
     }
 
     /// Reread the contents of the shader from the source file, using the
-    /// path given at creation.
+    /// path and defines given at creation. Shaders from memory are reset to their source.
     pub fn reload(&mut self) {
-        let path = &self.metadata.path;
-        *self = Self::load(path, self.metadata.backend);
+        let generated_fallback = self.generated_fallback.take();
+        *self = Self::from_metadata(self.metadata.clone());
+
+        // Keep the interface of the last version that could be parsed
+        if self.generated_fallback.is_none() {
+            self.generated_fallback = generated_fallback;
+        }
+    }
+
+    /// Returns whether the source was given in memory instead of read from [`ShaderSource::path`].
+    pub fn is_embedded(&self) -> bool {
+        self.metadata.embedded.is_some()
     }
 
     /// Returns whether the shader source is fallback.
@@ -88,77 +345,376 @@ impl ShaderSource {
         self.source = None;
     }
 
-    pub fn backend(&self) -> ShaderBackend {
-        self.metadata.backend
+    /// Registers a WGSL shader to use instead of the generated fallback. It must be compatible
+    /// with the pipelines the shader is used in. Fallbacks that don't pass naga validation are
+    /// logged and ignored.
+    pub fn with_fallback(mut self, wgsl: &str) -> Self {
+        match fallback::validate(wgsl) {
+            Ok(()) => self.metadata.fallback = Some(wgsl.to_owned()),
+            Err(e) => log::error!(
+                "Fallback registered for shader {} is invalid: {}",
+                self.metadata.name,
+                e
+            ),
+        }
+
+        self
+    }
+
+    /// Returns the WGSL source of the fallback shader, in order of preference:
+    /// 1. the fallback registered with [`ShaderSource::with_fallback`]
+    /// 2. a fallback with the entry points, inputs and outputs of the last version of the source
+    ///    that could be parsed
+    /// 3. a generic fallback with `vertex`, `fragment` and `compute` entry points
+    pub fn fallback_source(&self) -> &str {
+        self.metadata
+            .fallback
+            .as_deref()
+            .or(self.generated_fallback.as_deref())
+            .unwrap_or(fallback::DEFAULT)
+    }
+
+    /// Returns the error that made the source fall back while loading, e.g. a missing file or
+    /// a preprocessor error.
+    pub fn load_error(&self) -> Option<&Error> {
+        self.load_error.as_ref()
     }
 
-    fn source_str(&self) -> Option<&str> {
+    pub fn backend(&self) -> &ShaderBackend {
+        &self.metadata.backend
+    }
+
+    /// Text of a WGSL or GLSL source that isn't the fallback. Both are read as strings, so
+    /// they're always valid UTF-8.
+    fn source_str(&self) -> &str {
+        let source = self
+            .source
+            .as_deref()
+            .expect("Fallback shaders have no source");
+
         match self.backend() {
-            ShaderBackend::Wgsl => Some(std::str::from_utf8(self.source.as_ref()?).unwrap()),
-            ShaderBackend::Spirv => panic!("Can't get source strings for binary Spir-V format"),
+            ShaderBackend::Spirv | ShaderBackend::SpirvPassthrough => {
+                panic!("Can't get source strings for binary Spir-V format")
+            }
+            _ => std::str::from_utf8(source).expect("Text sources are read as strings"),
         }
     }
 
-    #[allow(unused)]
-    fn source_words(&self) -> Option<Cow<'_, [u32]>> {
+    /// Defines for wgpu's GLSL front-end, empty for other backends.
+    fn glsl_defines(&self) -> Vec<(&str, &str)> {
         match self.backend() {
-            ShaderBackend::Wgsl => panic!("Can't get source words for wgsl"),
-            ShaderBackend::Spirv => Some(wgpu::util::make_spirv_raw(self.source.as_ref()?)),
+            #[cfg(feature = "glsl")]
+            ShaderBackend::Glsl { defines, .. } => defines
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect(),
+            _ => Vec::new(),
         }
     }
 
-    fn descriptor(&self) -> ShaderModuleDescriptor {
-        match self.is_fallback() {
+    /// Returns the Spir-V words, after checking the size and magic number of the binary.
+    fn source_words(&self) -> Result<Cow<'_, [u32]>, Error> {
+        const MAGIC_NUMBER: u32 = 0x0723_0203;
+        const HEADER_WORDS: usize = 5;
+
+        let invalid = |message: String| -> Error {
+            ShaderError::InvalidSpirv {
+                name: self.metadata.name.clone(),
+                message,
+            }
+            .into()
+        };
+
+        let bytes = match (self.backend(), self.source.as_ref()) {
+            (ShaderBackend::Spirv | ShaderBackend::SpirvPassthrough, Some(bytes)) => bytes,
+            (ShaderBackend::Spirv | ShaderBackend::SpirvPassthrough, None) => {
+                return Err(invalid("fallback sources have no Spir-V words".to_owned()))
+            }
+            _ => return Err(invalid("not a Spir-V source".to_owned())),
+        };
+
+        if !bytes.len().is_multiple_of(4) {
+            return Err(invalid(format!(
+                "size of {} bytes is not a multiple of 4",
+                bytes.len()
+            )));
+        }
+
+        if bytes.len() < HEADER_WORDS * 4 {
+            return Err(invalid(format!(
+                "size of {} bytes is too small for the header",
+                bytes.len()
+            )));
+        }
+
+        let magic = u32::from_ne_bytes(bytes[..4].try_into().unwrap());
+        if magic != MAGIC_NUMBER {
+            return Err(invalid(match magic.swap_bytes() == MAGIC_NUMBER {
+                true => "byte order doesn't match the host".to_owned(),
+                false => format!("invalid magic number {magic:#010x}"),
+            }));
+        }
+
+        Ok(wgpu::util::make_spirv_raw(bytes))
+    }
+
+    /// `glsl_defines` are borrowed by the descriptor for GLSL sources, see
+    /// [`ShaderSource::glsl_defines`].
+    #[cfg_attr(not(feature = "glsl"), allow(unused_variables))]
+    fn descriptor<'a>(
+        &'a self,
+        glsl_defines: &'a [(&'a str, &'a str)],
+    ) -> Result<ShaderModuleDescriptor<'a>, Error> {
+        let descriptor = match self.is_fallback() {
             false => match self.backend() {
                 ShaderBackend::Wgsl => {
-                    let source_str = self.source_str();
-
-                    ShaderModuleDescriptor::Wgsl(wgpu::ShaderModuleDescriptor {
+                    ShaderModuleDescriptor::Checked(wgpu::ShaderModuleDescriptor {
                         label: Some(&self.metadata.name),
-                        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source_str.unwrap())),
+                        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(self.source_str())),
+                    })
+                }
+                #[cfg(feature = "glsl")]
+                ShaderBackend::Glsl { stage, .. } => {
+                    ShaderModuleDescriptor::Checked(wgpu::ShaderModuleDescriptor {
+                        label: Some(&self.metadata.name),
+                        source: wgpu::ShaderSource::Glsl {
+                            shader: Cow::Borrowed(self.source_str()),
+                            stage: *stage,
+                            defines: glsl_defines,
+                        },
                     })
                 }
                 ShaderBackend::Spirv => {
-                    let source_words = self.source_words();
-
-                    ShaderModuleDescriptor::Passthrough(
-                        wgpu::ShaderModuleDescriptorPassthrough::SpirV(
-                            wgpu::ShaderModuleDescriptorSpirV {
-                                label: Some(&self.metadata.name),
-                                source: source_words.unwrap(),
-                            },
-                        ),
-                    )
+                    ShaderModuleDescriptor::Checked(wgpu::ShaderModuleDescriptor {
+                        label: Some(&self.metadata.name),
+                        source: wgpu::ShaderSource::SpirV(self.source_words()?),
+                    })
                 }
+                ShaderBackend::SpirvPassthrough => ShaderModuleDescriptor::Passthrough(
+                    wgpu::ShaderModuleDescriptorPassthrough::SpirV(
+                        wgpu::ShaderModuleDescriptorSpirV {
+                            label: Some(&self.metadata.name),
+                            source: self.source_words()?,
+                        },
+                    ),
+                ),
             },
-            true => ShaderModuleDescriptor::Wgsl(self.fallback_descriptor()),
+            true => ShaderModuleDescriptor::Checked(self.fallback_descriptor()),
+        };
+
+        Ok(descriptor)
+    }
+
+    /// Returns the name of the shader, derived from its file name.
+    pub fn name(&self) -> &str {
+        &self.metadata.name
+    }
+
+    /// Returns the defines the shader was preprocessed with, not including those defined by
+    /// `#define` in the source.
+    pub fn defines(&self) -> &BTreeMap<String, String> {
+        &self.metadata.defines
+    }
+
+    /// Returns the path the shader was loaded from, or its name for shaders from memory.
+    pub fn path(&self) -> &Path {
+        &self.metadata.path
+    }
+
+    /// Returns the paths of all files the shader source was read from, including the files
+    /// it includes.
+    ///
+    /// For shaders from memory, only the included files are returned.
+    pub fn dependencies(&self) -> Vec<&Path> {
+        let skip = match self.is_embedded() {
+            true => 1,
+            false => 0,
+        };
+
+        match self.preprocessed.files.is_empty() {
+            true if self.is_embedded() => Vec::new(),
+            true => vec![&self.metadata.path],
+            false => self
+                .preprocessed
+                .files
+                .iter()
+                .skip(skip)
+                .map(|p| p.as_path())
+                .collect(),
+        }
+    }
+
+    /// Maps a 1-based line number of the source (with includes resolved) to the file and line
+    /// it originally came from.
+    pub fn original_location(&self, line: u32) -> Option<(&Path, u32)> {
+        self.preprocessed.original_location(line)
+    }
+
+    /// Builds an [`Error::ShaderCompile`] from a WGSL parse error.
+    fn wgsl_parse_error(&self, error: &naga::front::wgsl::ParseError, source: &str) -> Error {
+        self.compile_error(
+            source,
+            error.message().to_owned(),
+            error.labels(),
+            Vec::new(),
+        )
+    }
+
+    /// Builds an [`Error::ShaderCompile`] from GLSL parse errors, labeling each span with its
+    /// error.
+    #[cfg(feature = "glsl")]
+    fn glsl_parse_error(&self, errors: &naga::front::glsl::ParseErrors, source: &str) -> Error {
+        let messages: Vec<_> = errors.errors.iter().map(|e| e.kind.to_string()).collect();
+        let labels = errors
+            .errors
+            .iter()
+            .zip(&messages)
+            .map(|(e, message)| (e.meta, message.as_str()));
+
+        let message = match messages.len() {
+            1 => messages[0].clone(),
+            n => format!("{n} errors"),
+        };
+
+        self.compile_error(source, message, labels, Vec::new())
+    }
+
+    /// Parses the shader source (or the fallback shader) into a [`naga::Module`] for reflection.
+    pub fn reflect(&self) -> Result<naga::Module, Error> {
+        if self.is_fallback() {
+            let source = self.fallback_source();
+            return naga::front::wgsl::parse_str(source).map_err(|e| Error::ShaderReflection {
+                name: self.metadata.name.clone(),
+                message: e.emit_to_string(source),
+            });
+        }
+
+        match self.backend() {
+            ShaderBackend::Wgsl => {
+                let source = self.source_str();
+                naga::front::wgsl::parse_str(source).map_err(|e| self.wgsl_parse_error(&e, source))
+            }
+            ShaderBackend::Spirv | ShaderBackend::SpirvPassthrough => {
+                let words = self.source_words()?;
+
+                naga::front::spv::Frontend::new(
+                    words.iter().copied(),
+                    &naga::front::spv::Options::default(),
+                )
+                .parse()
+                .map_err(|e| self.compile_error("", e.to_string(), std::iter::empty(), Vec::new()))
+            }
+            #[cfg(feature = "glsl")]
+            ShaderBackend::Glsl { stage, defines } => {
+                let source = self.source_str();
+                let options = naga::front::glsl::Options {
+                    stage: *stage,
+                    defines: defines
+                        .iter()
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect(),
+                };
+
+                naga::front::glsl::Frontend::default()
+                    .parse(&options, source)
+                    .map_err(|e| self.glsl_parse_error(&e, source))
+            }
         }
     }
 
+    /// Parses and validates the shader source with naga, allowing the given capabilities.
+    /// Errors are returned as [`Error::ShaderCompile`] with their spans in the original files.
+    pub fn validate(
+        &self,
+        capabilities: naga::valid::Capabilities,
+    ) -> Result<(naga::Module, naga::valid::ModuleInfo), Error> {
+        let module = self.reflect()?;
+
+        let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), capabilities)
+            .validate(&module)
+            .map_err(|e| {
+                let source = match self.backend() {
+                    ShaderBackend::Spirv | ShaderBackend::SpirvPassthrough => "",
+                    _ if self.is_fallback() => "",
+                    _ => self.source_str(),
+                };
+
+                let mut notes = Vec::new();
+                let mut cause = std::error::Error::source(&e);
+                while let Some(next) = cause {
+                    notes.push(next.to_string());
+                    cause = next.source();
+                }
+
+                let labels = e.spans().map(|(span, label)| (*span, label.as_str()));
+                self.compile_error(source, e.to_string(), labels, notes)
+            })?;
+
+        Ok((module, info))
+    }
+
     /// Returns the shader module descriptor used for fallback shaders.
     pub fn fallback_descriptor(&self) -> wgpu::ShaderModuleDescriptor<'_> {
         wgpu::ShaderModuleDescriptor {
             label: Some(&self.metadata.name),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("assets/fallback.wgsl"))),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(self.fallback_source())),
         }
     }
 }
 
-/// Creates a [`wgpu::ShaderModule`] given the [`ShaderSource`]. If creation fails for whatever
-/// reason (e.g. compile error), then a [`wgpu::Error`] validation error is returned containing
-/// the description of the error.
+/// Labels a shader name with its defines, or the GLSL defines of its backend.
+fn labeled_name(
+    name: String,
+    backend: &ShaderBackend,
+    defines: &BTreeMap<String, String>,
+) -> String {
+    match backend {
+        #[cfg(feature = "glsl")]
+        ShaderBackend::Glsl { defines, .. } => variant_name(name, defines),
+        _ => variant_name(name, defines),
+    }
+}
+
+/// Appends the define set to a shader name, so variants get distinct labels.
+fn variant_name(name: String, defines: &BTreeMap<String, String>) -> String {
+    if defines.is_empty() {
+        return name;
+    }
+
+    let defines: Vec<_> = defines
+        .iter()
+        .map(|(k, v)| match v.is_empty() {
+            true => k.clone(),
+            false => format!("{k}={v}"),
+        })
+        .collect();
+
+    format!("{}[{}]", name, defines.join(","))
+}
+
+/// Creates a [`wgpu::ShaderModule`] given the [`ShaderSource`]. Sources other than passthrough
+/// Spir-V are validated with naga first, returning [`Error::ShaderCompile`] for parse and
+/// validation errors. If creation still fails for whatever reason, then a [`wgpu::Error`]
+/// validation error is returned containing the description of the error.
 ///
 /// Either handle the error accordingly, or call [`ShaderSource::make_fallback`] on the source,
 /// and then call this function again to create a fallback (basically empty) shader module.
 pub fn create(device: &wgpu::Device, source: &ShaderSource) -> Result<wgpu::ShaderModule, Error> {
-    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let glsl_defines = source.glsl_defines();
+
+    let module = match source.descriptor(&glsl_defines)? {
+        ShaderModuleDescriptor::Checked(desc) => {
+            // Check with naga first for a readable error. Capabilities are left to wgpu, which
+            // knows the device's features.
+            if !source.is_fallback() {
+                source.validate(naga::valid::Capabilities::all())?;
+            }
 
-    let module = match source.descriptor() {
-        ShaderModuleDescriptor::Wgsl(desc) => {
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
             let module = device.create_shader_module(desc);
 
-            // Error check only for wgsl, because passthrough shaders will not generate wgpu-level errors
-            // instead, they will crash the driver or similar
+            // Error check only for translated shaders, because passthrough shaders will not
+            // generate wgpu-level errors instead, they will crash the driver or similar
             let compile_error = pollster::block_on(device.pop_error_scope());
             if let Some(error) = compile_error {
                 return Err(error.into());
@@ -166,15 +722,23 @@ pub fn create(device: &wgpu::Device, source: &ShaderSource) -> Result<wgpu::Shad
 
             module
         }
-        ShaderModuleDescriptor::Passthrough(desc) => unsafe {
-            device.create_shader_module_passthrough(desc)
-        },
+        ShaderModuleDescriptor::Passthrough(desc) => {
+            let feature = wgpu::Features::SPIRV_SHADER_PASSTHROUGH;
+            if !device.features().contains(feature) {
+                return Err(Error::MissingFeatures(feature));
+            }
+
+            // Safety: the caller opted in with `ShaderSource::load_spirv_passthrough`
+            unsafe { device.create_shader_module_passthrough(desc) }
+        }
     };
 
     Ok(module)
 }
 
 /// Attempts to create the shader module, and if it fails, automatically creates a fallback shader.
+/// If the fallback fails too, e.g. because it needs a feature the device doesn't have, the
+/// generic fallback described in [`ShaderSource::fallback_source`] is used.
 pub fn create_or_fallback(
     device: &wgpu::Device,
     source: &mut ShaderSource,
@@ -183,7 +747,57 @@ pub fn create_or_fallback(
         Ok(s) => (s, None),
         Err(e) => {
             source.make_fallback();
-            (create(device, source).unwrap(), Some(e))
+
+            let module = create(device, source).unwrap_or_else(|fallback_error| {
+                log::warn!(
+                    "Fallback for shader {} failed, using the default fallback: {}",
+                    source.name(),
+                    fallback_error
+                );
+
+                device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(source.name()),
+                    source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(fallback::DEFAULT)),
+                })
+            });
+
+            (module, Some(e))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_prefers_disk() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/assets/fallback.wgsl");
+        let embedded = include_str!("assets/fallback.wgsl");
+
+        assert!(ShaderSource::load_wgsl_embedded(path, embedded, false).is_embedded());
+        assert!(!ShaderSource::load_wgsl_embedded(path, embedded, true).is_embedded());
+        assert!(ShaderSource::load_wgsl_embedded("missing.wgsl", embedded, true).is_embedded());
+
+        let shader = crate::embed_wgsl!("src/assets/fallback.wgsl");
+        assert_eq!(shader.is_embedded(), !cfg!(debug_assertions));
+        assert!(shader.load_error().is_none());
+    }
+
+    #[cfg(feature = "glsl")]
+    #[test]
+    fn glsl_invalid_utf8() {
+        let dir = crate::util::TempDir::new();
+        let path = dir.write("invalid.frag", b"void main() {} // \xff\xfe");
+
+        let source = ShaderSource::load_glsl(
+            &path,
+            naga::ShaderStage::Fragment,
+            std::iter::empty::<(String, String)>(),
+        );
+
+        assert!(source.is_fallback());
+        assert!(matches!(source.load_error(), Some(Error::Io(_))));
+        source.validate(naga::valid::Capabilities::all()).unwrap();
+    }
+}
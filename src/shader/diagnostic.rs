@@ -0,0 +1,186 @@
+use std::{fmt::Write, path::PathBuf};
+
+use crate::Error;
+
+use super::ShaderSource;
+
+/// A labeled range of a shader's source, located in the original file it came from.
+#[derive(Clone, Debug)]
+pub struct SourceSpan {
+    pub path: PathBuf,
+    /// 1-based line number.
+    pub line: u32,
+    /// 1-based column, in bytes.
+    pub column: u32,
+    /// Length in bytes, on the first line of the span.
+    pub length: u32,
+    pub label: String,
+}
+
+impl ShaderSource {
+    /// Builds an [`Error::ShaderCompile`] from a naga error's message and labeled spans in
+    /// `source`, which is the preprocessed source the error refers to.
+    pub(crate) fn compile_error<'a>(
+        &self,
+        source: &str,
+        message: String,
+        labels: impl Iterator<Item = (naga::Span, &'a str)>,
+        notes: Vec<String>,
+    ) -> Error {
+        let mut spans = Vec::new();
+        let mut lines = Vec::new();
+
+        for (span, label) in labels {
+            // Binary sources have no text to point into
+            if span.to_range().is_none_or(|range| range.end > source.len()) {
+                continue;
+            }
+
+            let location = span.location(source);
+            let text = source
+                .lines()
+                .nth(location.line_number as usize - 1)
+                .unwrap_or_default();
+
+            let (path, line) = self
+                .original_location(location.line_number)
+                .map(|(path, line)| (path.to_owned(), line))
+                .unwrap_or_else(|| (self.metadata.path.clone(), location.line_number));
+
+            // Spans over multiple lines are only underlined on their first line
+            let available = (text.len() + 1).saturating_sub(location.line_position as usize);
+            let length = (location.length as usize).clamp(1, available.max(1)) as u32;
+
+            spans.push(SourceSpan {
+                path,
+                line,
+                column: location.line_position,
+                length,
+                label: label.to_owned(),
+            });
+            lines.push(text.to_owned());
+        }
+
+        let rendered = render(&message, &spans, &lines, &notes);
+
+        Error::ShaderCompile {
+            name: self.metadata.name.clone(),
+            path: self.metadata.path.clone(),
+            message,
+            spans,
+            rendered,
+        }
+    }
+}
+
+/// Renders a diagnostic in the style of codespan-reporting, with every span shown in the file
+/// it originally came from.
+fn render(message: &str, spans: &[SourceSpan], lines: &[String], notes: &[String]) -> String {
+    let gutter = spans
+        .iter()
+        .map(|span| span.line.to_string().len())
+        .max()
+        .unwrap_or(1);
+    let pad = " ".repeat(gutter);
+
+    let mut out = format!("error: {message}\n");
+
+    for (span, text) in spans.iter().zip(lines) {
+        let _ = writeln!(
+            out,
+            "{pad} ┌─ {}:{}:{}",
+            span.path.display(),
+            span.line,
+            span.column
+        );
+        let _ = writeln!(out, "{pad} │");
+        let _ = writeln!(out, "{:>gutter$} │ {}", span.line, text);
+        let _ = writeln!(
+            out,
+            "{pad} │ {}{} {}",
+            " ".repeat(span.column as usize - 1),
+            "^".repeat(span.length as usize),
+            span.label
+        );
+        let _ = writeln!(out, "{pad} │");
+    }
+
+    for note in notes {
+        let _ = writeln!(out, "{pad} = {note}");
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::util::TempDir;
+
+    fn compile_error(source: &ShaderSource) -> (Vec<SourceSpan>, String) {
+        match source.validate(naga::valid::Capabilities::default()) {
+            Err(Error::ShaderCompile {
+                spans, rendered, ..
+            }) => (spans, rendered),
+            other => panic!("expected a compile error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        let source =
+            ShaderSource::from_wgsl_str("test.wgsl", "fn main() {\n    let x: f32 = 1u;\n}\n");
+        let (spans, rendered) = compile_error(&source);
+
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].path, Path::new("test.wgsl"));
+        assert_eq!((spans[0].line, spans[0].column, spans[0].length), (2, 9, 1));
+
+        assert_eq!(
+            rendered,
+            "\
+error: the type of `x` is expected to be `f32`, but got `u32`
+  ┌─ test.wgsl:2:9
+  │
+2 │     let x: f32 = 1u;
+  │         ^ definition of `x`
+  │
+"
+        );
+    }
+
+    #[test]
+    fn validation_error_in_include() {
+        let dir = TempDir::new();
+        dir.write(
+            "common.wgsl",
+            "// common\nfn f() -> f32 {\n    return 1u;\n}\n",
+        );
+
+        let main = dir.path().join("main.wgsl");
+        let source = ShaderSource::from_wgsl_str(
+            main.to_str().unwrap(),
+            "// main\n#include \"common.wgsl\"\n",
+        );
+        let (spans, rendered) = compile_error(&source);
+
+        let common = std::fs::canonicalize(dir.path().join("common.wgsl")).unwrap();
+        let locations: Vec<_> = spans
+            .iter()
+            .map(|span| (span.path.as_path(), span.line, span.column, span.length))
+            .collect();
+        assert_eq!(
+            locations,
+            [(common.as_path(), 2, 1, 15), (common.as_path(), 3, 12, 2)]
+        );
+
+        let expected = format!(
+            "  ┌─ {}:3:12\n  │\n3 │     return 1u;\n  │            ^^ ",
+            common.display()
+        );
+        assert!(rendered.contains(&expected), "{rendered}");
+        assert!(rendered.ends_with("does not match the function return value\n"));
+    }
+}
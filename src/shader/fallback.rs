@@ -0,0 +1,383 @@
+use std::{collections::BTreeSet, fmt::Write};
+
+use naga::{Binding, Interpolation, Module, Sampling, ScalarKind, ShaderStage, TypeInner};
+
+/// An input or output with a `@location` binding.
+struct LocationIo {
+    location: u32,
+    ty: String,
+    /// `@interpolate` and `@blend_src` attributes.
+    attributes: String,
+}
+
+/// Generic fallback with `vertex`, `fragment` and `compute` entry points.
+pub(crate) const DEFAULT: &str = include_str!("../assets/fallback.wgsl");
+
+/// Parses and validates a fallback shader. Capabilities are left to wgpu, like in
+/// [`create`](super::create).
+pub(crate) fn validate(source: &str) -> Result<(), String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| e.emit_to_string(source))?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| e.emit_to_string(source))?;
+
+    Ok(())
+}
+
+/// Generates a fallback shader with the same interface as `module`, so pipelines with explicit
+/// layouts built against the original can use it instead:
+/// - entry points with the same names and stages, and the same workgroup sizes
+/// - the same vertex inputs and outputs, and fragment outputs (which are red for float targets)
+/// - the same pipeline-overridable constants, so constants set by the pipeline exist
+///
+/// Resource bindings aren't replicated, so pipelines using `layout: None` get a different layout
+/// from the fallback. Returns `None` if the interface can't be expressed, e.g. for mesh shaders.
+pub(crate) fn generate(module: &Module) -> Option<String> {
+    let mut enables = BTreeSet::new();
+    let mut out = String::new();
+
+    for (_, o) in module.overrides.iter() {
+        let ty = type_name(module, o.ty, &mut enables)?;
+        let name = match (&o.name, o.id) {
+            (Some(name), _) => name.clone(),
+            (None, Some(id)) => format!("override_{id}"),
+            (None, None) => continue,
+        };
+
+        if let Some(id) = o.id {
+            let _ = write!(out, "@id({id}) ");
+        }
+        let _ = writeln!(out, "override {name}: {ty} = {ty}();");
+    }
+
+    for (i, entry) in module.entry_points.iter().enumerate() {
+        let function = &entry.function;
+        let name = &entry.name;
+
+        out.push('\n');
+
+        match entry.stage {
+            ShaderStage::Vertex => {
+                let mut inputs = Vec::new();
+                for argument in &function.arguments {
+                    location_io(
+                        module,
+                        argument.ty,
+                        argument.binding.as_ref(),
+                        &mut inputs,
+                        &mut enables,
+                    )?;
+                }
+
+                let mut outputs = Vec::new();
+                if let Some(result) = &function.result {
+                    location_io(
+                        module,
+                        result.ty,
+                        result.binding.as_ref(),
+                        &mut outputs,
+                        &mut enables,
+                    )?;
+                }
+
+                let output_struct = format!("FallbackVertexOutput{i}");
+                let _ = writeln!(out, "struct {output_struct} {{");
+                let _ = writeln!(out, "    @builtin(position) position: vec4<f32>,");
+                for (j, output) in outputs.iter().enumerate() {
+                    let _ = writeln!(
+                        out,
+                        "    @location({}){} out_{j}: {},",
+                        output.location, output.attributes, output.ty
+                    );
+                }
+                let _ = writeln!(out, "}}\n");
+
+                let inputs: Vec<_> = inputs
+                    .iter()
+                    .map(|input| {
+                        format!(
+                            "@location({}) in_{}: {}",
+                            input.location, input.location, input.ty
+                        )
+                    })
+                    .collect();
+
+                let _ = writeln!(out, "@vertex");
+                let _ = writeln!(
+                    out,
+                    "fn {name}({}) -> {output_struct} {{",
+                    inputs.join(", ")
+                );
+                let _ = writeln!(out, "    var out: {output_struct};");
+                let _ = writeln!(out, "    out.position = vec4(1.0);");
+                let _ = writeln!(out, "    return out;");
+                let _ = writeln!(out, "}}");
+            }
+            ShaderStage::Fragment => {
+                let mut outputs = Vec::new();
+                if let Some(result) = &function.result {
+                    location_io(
+                        module,
+                        result.ty,
+                        result.binding.as_ref(),
+                        &mut outputs,
+                        &mut enables,
+                    )?;
+                }
+
+                if outputs.is_empty() {
+                    let _ = writeln!(out, "@fragment\nfn {name}() {{}}");
+                    continue;
+                }
+
+                let output_struct = format!("FallbackFragmentOutput{i}");
+                let _ = writeln!(out, "struct {output_struct} {{");
+                for (j, output) in outputs.iter().enumerate() {
+                    let _ = writeln!(
+                        out,
+                        "    @location({}){} out_{j}: {},",
+                        output.location, output.attributes, output.ty
+                    );
+                }
+                let _ = writeln!(out, "}}\n");
+
+                let _ = writeln!(out, "@fragment");
+                let _ = writeln!(out, "fn {name}() -> {output_struct} {{");
+                let _ = writeln!(out, "    var out: {output_struct};");
+                for (j, output) in outputs.iter().enumerate() {
+                    if output.ty == "vec4<f32>" {
+                        let _ = writeln!(out, "    out.out_{j} = vec4(1.0, 0.0, 0.0, 1.0);");
+                    }
+                }
+                let _ = writeln!(out, "    return out;");
+                let _ = writeln!(out, "}}");
+            }
+            ShaderStage::Compute => {
+                let [x, y, z] = entry.workgroup_size.map(|size| size.max(1));
+
+                let _ = writeln!(
+                    out,
+                    "@compute\n@workgroup_size({x}, {y}, {z})\nfn {name}() {{}}"
+                );
+            }
+            ShaderStage::Task | ShaderStage::Mesh => return None,
+        }
+    }
+
+    let enables: String = enables.iter().map(|e| format!("enable {e};\n")).collect();
+    Some(enables + &out)
+}
+
+/// Collects the `@location` inputs or outputs of a binding, or of the members of a struct
+/// without a binding. Built-ins are skipped.
+fn location_io(
+    module: &Module,
+    ty: naga::Handle<naga::Type>,
+    binding: Option<&Binding>,
+    io: &mut Vec<LocationIo>,
+    enables: &mut BTreeSet<&'static str>,
+) -> Option<()> {
+    match binding {
+        Some(Binding::Location {
+            location,
+            interpolation,
+            sampling,
+            blend_src,
+        }) => {
+            let mut attributes = String::new();
+
+            if let Some(interpolation) = interpolation {
+                let interpolation = match interpolation {
+                    Interpolation::Perspective => "perspective",
+                    Interpolation::Linear => "linear",
+                    Interpolation::Flat => "flat",
+                };
+
+                let sampling = sampling.map(|sampling| match sampling {
+                    Sampling::Center => "center",
+                    Sampling::Centroid => "centroid",
+                    Sampling::Sample => "sample",
+                    Sampling::First => "first",
+                    Sampling::Either => "either",
+                });
+
+                match sampling {
+                    Some(sampling) => {
+                        let _ = write!(attributes, " @interpolate({interpolation}, {sampling})");
+                    }
+                    None => {
+                        let _ = write!(attributes, " @interpolate({interpolation})");
+                    }
+                }
+            }
+
+            if let Some(blend_src) = blend_src {
+                enables.insert("dual_source_blending");
+                let _ = write!(attributes, " @blend_src({blend_src})");
+            }
+
+            io.push(LocationIo {
+                location: *location,
+                ty: type_name(module, ty, enables)?,
+                attributes,
+            });
+        }
+        Some(Binding::BuiltIn(_)) => {}
+        None => {
+            let TypeInner::Struct { members, .. } = &module.types[ty].inner else {
+                return None;
+            };
+
+            for member in members {
+                location_io(module, member.ty, member.binding.as_ref(), io, enables)?;
+            }
+        }
+    }
+
+    Some(())
+}
+
+/// Returns the WGSL name of a scalar or vector type, the only types allowed for shader IO and
+/// overrides.
+fn type_name(
+    module: &Module,
+    ty: naga::Handle<naga::Type>,
+    enables: &mut BTreeSet<&'static str>,
+) -> Option<String> {
+    let (scalar, size) = match module.types[ty].inner {
+        TypeInner::Scalar(scalar) => (scalar, None),
+        TypeInner::Vector { size, scalar } => (scalar, Some(size as u8)),
+        _ => return None,
+    };
+
+    let scalar = match (scalar.kind, scalar.width) {
+        (ScalarKind::Float, 4) => "f32",
+        (ScalarKind::Float, 2) => {
+            enables.insert("f16");
+            "f16"
+        }
+        (ScalarKind::Sint, 4) => "i32",
+        (ScalarKind::Uint, 4) => "u32",
+        (ScalarKind::Bool, _) => "bool",
+        _ => return None,
+    };
+
+    Some(match size {
+        Some(size) => format!("vec{size}<{scalar}>"),
+        None => scalar.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_str(source: &str) -> String {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        let fallback = generate(&module).unwrap();
+
+        if let Err(e) = validate(&fallback) {
+            panic!("invalid fallback:\n{fallback}\n{e}");
+        }
+
+        fallback
+    }
+
+    fn entry_points(source: &str) -> Vec<(String, ShaderStage, [u32; 3])> {
+        naga::front::wgsl::parse_str(source)
+            .unwrap()
+            .entry_points
+            .into_iter()
+            .map(|e| (e.name, e.stage, e.workgroup_size))
+            .collect()
+    }
+
+    #[test]
+    fn default_is_valid() {
+        validate(DEFAULT).unwrap();
+    }
+
+    #[test]
+    fn render_interface() {
+        let source = "
+            struct VertexOutput {
+                @builtin(position) position: vec4<f32>,
+                @location(0) uv: vec2<f32>,
+                @location(2) @interpolate(flat) id: u32,
+            }
+
+            @group(0) @binding(0) var<uniform> offset: vec4<f32>;
+            override scale: f32 = 2.0;
+            @id(3) override count: u32;
+
+            @vertex
+            fn vs_main(@location(0) position: vec3<f32>, @location(1) uv: vec2<f32>,
+                       @builtin(vertex_index) index: u32) -> VertexOutput {
+                return VertexOutput(vec4(position * scale, 1.0) + offset, uv, index + count);
+            }
+
+            @fragment
+            fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+                return vec4(input.uv, 0.0, 1.0);
+            }
+        ";
+
+        let fallback = generate_str(source);
+        assert_eq!(entry_points(&fallback), entry_points(source));
+
+        let module = naga::front::wgsl::parse_str(&fallback).unwrap();
+        let overrides: Vec<_> = module
+            .overrides
+            .iter()
+            .map(|(_, o)| (o.name.clone().unwrap(), o.id))
+            .collect();
+        assert_eq!(
+            overrides,
+            [("scale".to_owned(), None), ("count".to_owned(), Some(3))]
+        );
+    }
+
+    #[test]
+    fn compute_workgroup_size() {
+        let source = "@compute @workgroup_size(8, 4) fn main() {}";
+        assert_eq!(entry_points(&generate_str(source)), entry_points(source));
+    }
+
+    #[test]
+    fn f16_interface() {
+        let source = "
+            enable f16;
+
+            @fragment
+            fn main(@location(0) color: vec4<f16>) -> @location(0) vec4<f16> {
+                return color;
+            }
+        ";
+
+        let fallback = generate_str(source);
+        assert!(fallback.starts_with("enable f16;"), "{fallback}");
+    }
+
+    #[test]
+    fn dual_source_blending() {
+        let source = "
+            enable dual_source_blending;
+
+            struct Output {
+                @location(0) @blend_src(0) color: vec4<f32>,
+                @location(0) @blend_src(1) blend: vec4<f32>,
+            }
+
+            @fragment
+            fn main() -> Output {
+                return Output(vec4(1.0), vec4(0.5));
+            }
+        ";
+
+        generate_str(source);
+    }
+}
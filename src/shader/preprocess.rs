@@ -0,0 +1,444 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, ShaderError};
+
+/// Location in an original source file, for a line of preprocessed output.
+#[derive(Clone, Copy, Debug)]
+struct LineOrigin {
+    /// Index into [`Preprocessed::files`].
+    file: usize,
+    /// 1-based line number.
+    line: u32,
+}
+
+/// WGSL source with all includes and conditionals resolved.
+#[derive(Default)]
+pub(crate) struct Preprocessed {
+    pub source: String,
+
+    /// Every file read, starting with the root file. Also the dependencies of the shader.
+    pub files: Vec<PathBuf>,
+    /// Origin of each line of `source`.
+    lines: Vec<LineOrigin>,
+}
+
+impl Preprocessed {
+    /// Maps a 1-based line number in the preprocessed source to the file and line it came from.
+    pub fn original_location(&self, line: u32) -> Option<(&Path, u32)> {
+        let origin = self.lines.get(line.checked_sub(1)? as usize)?;
+        Some((&self.files[origin.file], origin.line))
+    }
+}
+
+/// State of an `#ifdef`/`#ifndef` block.
+struct Conditional {
+    /// Whether the lines of the current branch are emitted.
+    active: bool,
+    /// Whether the enclosing block is active, which `#else` can't override.
+    parent_active: bool,
+    seen_else: bool,
+}
+
+struct Preprocessor<'a> {
+    defines: BTreeMap<String, String>,
+
+    /// Files currently being included, for cycle detection.
+    stack: Vec<PathBuf>,
+    output: &'a mut Preprocessed,
+}
+
+/// Resolves preprocessor directives:
+/// - `#include "path"`, with paths relative to the including file. Every file is only included
+///   once, as if it had an include guard, and include cycles are reported as errors.
+/// - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`.
+/// - `#define NAME` and `#define NAME value`. Defines with a value are substituted wherever
+///   `NAME` appears as an identifier.
+///
+/// `source` is the text of the root file if it's not read from `path`, e.g. for shaders from
+/// memory. `defines` are the initial defines. On error, `output.files` still contains every file read
+/// so far, so they can be watched for changes.
+pub(crate) fn preprocess(
+    path: &Path,
+    source: Option<&str>,
+    defines: &BTreeMap<String, String>,
+    output: &mut Preprocessed,
+) -> Result<(), Error> {
+    let mut preprocessor = Preprocessor {
+        defines: defines.clone(),
+        stack: Vec::new(),
+        output,
+    };
+
+    preprocessor.include_file(path, source, None)
+}
+
+impl Preprocessor<'_> {
+    fn include_file(
+        &mut self,
+        path: &Path,
+        source: Option<&str>,
+        included_from: Option<(&Path, u32)>,
+    ) -> Result<(), Error> {
+        let include_error = |message: String| -> Error {
+            let (path, line) = included_from.unwrap_or((path, 0));
+
+            ShaderError::Preprocess {
+                path: path.to_owned(),
+                line,
+                message,
+            }
+            .into()
+        };
+
+        // Canonical paths so the same file reached through different relative paths is recognized
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+
+        if self.stack.contains(&canonical) {
+            return Err(include_error(format!(
+                "include cycle through {}",
+                path.display()
+            )));
+        }
+
+        if included_from.is_some() && self.output.files.contains(&canonical) {
+            return Ok(());
+        }
+
+        self.output.files.push(canonical.clone());
+        let file = self.output.files.len() - 1;
+
+        let source =
+            match source.map_or_else(|| std::fs::read_to_string(path), |s| Ok(s.to_owned())) {
+                Ok(source) => source,
+                Err(e) if included_from.is_none() => return Err(e.into()),
+                Err(e) => {
+                    return Err(include_error(format!(
+                        "failed to read {}: {}",
+                        path.display(),
+                        e
+                    )))
+                }
+            };
+
+        self.stack.push(canonical);
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i as u32 + 1;
+
+            let error = |message: &str| -> Error {
+                ShaderError::Preprocess {
+                    path: path.to_owned(),
+                    line: line_number,
+                    message: message.to_owned(),
+                }
+                .into()
+            };
+
+            let active = conditionals.last().is_none_or(|c| c.active);
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    self.emit(line, file, line_number);
+                }
+
+                continue;
+            };
+
+            let (name, args) = directive
+                .split_once(char::is_whitespace)
+                .unwrap_or((directive, ""));
+            let args = args.trim();
+
+            match name {
+                "ifdef" | "ifndef" => {
+                    if args.is_empty() {
+                        return Err(error("expected a define name"));
+                    }
+
+                    let defined = self.defines.contains_key(args);
+
+                    conditionals.push(Conditional {
+                        active: active && defined == (name == "ifdef"),
+                        parent_active: active,
+                        seen_else: false,
+                    });
+                }
+                "else" => {
+                    let conditional = conditionals
+                        .last_mut()
+                        .ok_or_else(|| error("#else without #ifdef or #ifndef"))?;
+
+                    if conditional.seen_else {
+                        return Err(error("more than one #else"));
+                    }
+
+                    conditional.seen_else = true;
+                    conditional.active = conditional.parent_active && !conditional.active;
+                }
+                "endif" => {
+                    conditionals
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef or #ifndef"))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) =
+                        args.split_once(char::is_whitespace).unwrap_or((args, ""));
+
+                    if define.is_empty() {
+                        return Err(error("expected a define name"));
+                    }
+
+                    self.defines
+                        .insert(define.to_owned(), value.trim().to_owned());
+                }
+                "include" => {
+                    let include = args
+                        .strip_prefix('"')
+                        .and_then(|r| r.strip_suffix('"'))
+                        .ok_or_else(|| error("expected #include \"path\""))?;
+
+                    let include_path = path.parent().unwrap_or(Path::new("")).join(include);
+                    self.include_file(&include_path, None, Some((path, line_number)))?;
+                }
+                _ => return Err(error(&format!("unknown directive #{name}"))),
+            }
+        }
+
+        if !conditionals.is_empty() {
+            return Err(ShaderError::Preprocess {
+                path: path.to_owned(),
+                line: source.lines().count() as u32,
+                message: "missing #endif".to_owned(),
+            }
+            .into());
+        }
+
+        self.stack.pop();
+
+        Ok(())
+    }
+
+    fn emit(&mut self, line: &str, file: usize, line_number: u32) {
+        substitute(line, &self.defines, &mut self.output.source);
+        self.output.source.push('\n');
+
+        self.output.lines.push(LineOrigin {
+            file,
+            line: line_number,
+        });
+    }
+}
+
+/// Appends `line` to `output`, replacing identifiers that are defines with a value.
+fn substitute(line: &str, defines: &BTreeMap<String, String>, output: &mut String) {
+    if defines.values().all(String::is_empty) {
+        output.push_str(line);
+        return;
+    }
+
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut rest = line;
+
+    while let Some(start) = rest.find(is_identifier) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest.find(|c| !is_identifier(c)).unwrap_or(rest.len());
+        let identifier = &rest[..end];
+
+        match defines.get(identifier) {
+            Some(value) if !value.is_empty() => output.push_str(value),
+            _ => output.push_str(identifier),
+        }
+
+        rest = &rest[end..];
+    }
+
+    output.push_str(rest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    /// Writes `files` to a fresh temporary directory, which is removed when dropped.
+    fn fixture(files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new();
+
+        for (file, contents) in files {
+            dir.write(file, contents);
+        }
+
+        dir
+    }
+
+    fn run(path: &Path, source: Option<&str>) -> (Preprocessed, Result<(), Error>) {
+        let mut output = Preprocessed::default();
+        let result = preprocess(path, source, &BTreeMap::new(), &mut output);
+        (output, result)
+    }
+
+    #[test]
+    fn includes_once() {
+        let dir = fixture(&[
+            ("common.wgsl", "const A = 1;"),
+            ("lighting.wgsl", "#include \"common.wgsl\"\nconst B = A;"),
+        ]);
+
+        let source = "#include \"common.wgsl\"\n#include \"lighting.wgsl\"\nconst C = B;";
+        let (output, result) = run(&dir.path().join("main.wgsl"), Some(source));
+        result.unwrap();
+
+        assert_eq!(output.source, "const A = 1;\nconst B = A;\nconst C = B;\n");
+        assert_eq!(output.files.len(), 3);
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = fixture(&[
+            ("a.wgsl", "#include \"b.wgsl\""),
+            ("b.wgsl", "\n#include \"a.wgsl\""),
+        ]);
+
+        let (output, result) = run(&dir.path().join("a.wgsl"), None);
+
+        match result {
+            Err(Error::Shader(ShaderError::Preprocess {
+                path,
+                line,
+                message,
+            })) => {
+                assert!(path.ends_with("b.wgsl"));
+                assert_eq!(line, 2);
+                assert!(message.contains("include cycle"), "{message}");
+            }
+            other => panic!("expected an include cycle error, got {other:?}"),
+        }
+
+        // Both files are dependencies, so fixing either is noticed
+        assert_eq!(output.files.len(), 2);
+    }
+
+    #[test]
+    fn missing_include() {
+        let dir = fixture(&[]);
+
+        let (_, result) = run(
+            &dir.path().join("main.wgsl"),
+            Some("\n\n#include \"missing.wgsl\""),
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::Shader(ShaderError::Preprocess { line: 3, .. }))
+        ));
+    }
+
+    #[test]
+    fn original_locations() {
+        let dir = fixture(&[("common.wgsl", "// common\nconst A = 1;")]);
+
+        let source = "// main\n#include \"common.wgsl\"\nconst B = A;";
+        let (output, result) = run(&dir.path().join("main.wgsl"), Some(source));
+        result.unwrap();
+
+        let location = |line| {
+            output
+                .original_location(line)
+                .map(|(path, line)| (path.file_name().unwrap().to_str().unwrap(), line))
+        };
+
+        assert_eq!(location(1), Some(("main.wgsl", 1)));
+        assert_eq!(location(2), Some(("common.wgsl", 1)));
+        assert_eq!(location(3), Some(("common.wgsl", 2)));
+        assert_eq!(location(4), Some(("main.wgsl", 3)));
+        assert_eq!(location(5), None);
+        assert_eq!(location(0), None);
+    }
+
+    fn run_with_defines(source: &str, defines: &[(&str, &str)]) -> Result<String, Error> {
+        let defines = defines
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let mut output = Preprocessed::default();
+        preprocess(Path::new("main.wgsl"), Some(source), &defines, &mut output)?;
+        Ok(output.source)
+    }
+
+    const NESTED: &str = "\
+#ifdef A
+a
+#ifndef B
+a_not_b
+#else
+a_b
+#endif
+#else
+not_a
+#ifdef B
+not_a_b
+#endif
+#endif";
+
+    #[test]
+    fn nested_conditionals() {
+        let cases: [(&[(&str, &str)], &str); 4] = [
+            (&[], "not_a\n"),
+            (&[("B", "")], "not_a\nnot_a_b\n"),
+            (&[("A", "")], "a\na_not_b\n"),
+            (&[("A", ""), ("B", "")], "a\na_b\n"),
+        ];
+
+        for (defines, expected) in cases {
+            assert_eq!(run_with_defines(NESTED, defines).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn unbalanced_conditionals() {
+        for source in [
+            "#ifdef A\n",
+            "#endif",
+            "#else",
+            "#ifdef A\n#else\n#else\n#endif",
+        ] {
+            assert!(
+                matches!(
+                    run_with_defines(source, &[]),
+                    Err(Error::Shader(ShaderError::Preprocess { .. }))
+                ),
+                "{source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn define_substitution() {
+        let source = "\
+#define SIZE 64
+#ifdef FAST
+#define STEPS 4
+#endif
+const SIZE_2 = SIZE * 2;
+@workgroup_size(SIZE) fn f() { for (var i = 0; i < STEPS; i++) {} }";
+
+        assert_eq!(
+            run_with_defines(source, &[("FAST", ""), ("STEPS", "16")]).unwrap(),
+            "const SIZE_2 = 64 * 2;\n@workgroup_size(64) fn f() { for (var i = 0; i < 4; i++) {} }\n"
+        );
+        assert_eq!(
+            run_with_defines(source, &[("STEPS", "16")]).unwrap(),
+            "const SIZE_2 = 64 * 2;\n@workgroup_size(64) fn f() { for (var i = 0; i < 16; i++) {} }\n"
+        );
+    }
+}
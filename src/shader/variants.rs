@@ -0,0 +1,73 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+use super::ShaderSource;
+
+/// Compiled variants of one WGSL shader, keyed by the set of defines they were preprocessed
+/// with, so each combination is only compiled once.
+pub struct ShaderVariants {
+    path: PathBuf,
+    modules: HashMap<BTreeMap<String, String>, wgpu::ShaderModule>,
+}
+
+impl ShaderVariants {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            modules: HashMap::new(),
+        }
+    }
+
+    /// Returns the module compiled with these defines, loading and compiling it with
+    /// [`super::create`] if it isn't cached yet. Failed variants aren't cached.
+    pub fn get_or_create<I, K, V>(
+        &mut self,
+        device: &wgpu::Device,
+        defines: I,
+    ) -> Result<wgpu::ShaderModule, Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let defines: BTreeMap<String, String> = defines
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+
+        if let Some(module) = self.modules.get(&defines) {
+            return Ok(module.clone());
+        }
+
+        let mut source = ShaderSource::load_wgsl_with_defines(&self.path, defines.clone());
+        if let Some(e) = source.load_error.take() {
+            return Err(e);
+        }
+
+        let module = super::create(device, &source)?;
+
+        self.modules.insert(defines, module.clone());
+        Ok(module)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// Drops all compiled variants, e.g. after the source file changed.
+    pub fn clear(&mut self) {
+        self.modules.clear();
+    }
+}
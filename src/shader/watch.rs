@@ -0,0 +1,187 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use notify::Watcher;
+
+use crate::Error;
+
+use super::ShaderSource;
+
+struct WatchedShader {
+    source: ShaderSource,
+    module: wgpu::ShaderModule,
+
+    /// Absolute paths of the files the shader was read from.
+    dependencies: Vec<PathBuf>,
+}
+
+/// Owns a set of [`ShaderSource`]s and their compiled modules, and reloads them when their files
+/// change on disk. Available with the `hot-reload` feature.
+///
+/// Shaders are identified by their [`ShaderSource::name`]. Change events are debounced, so a
+/// shader is only reloaded once its files have stopped changing for the debounce duration.
+pub struct ShaderWatcher {
+    watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+
+    /// Directories are watched instead of files, since editors often replace files on save.
+    watched_dirs: HashSet<PathBuf>,
+
+    shaders: HashMap<String, WatchedShader>,
+    /// Time of the last change event for each shader that needs to be reloaded.
+    pending: HashMap<String, Instant>,
+
+    debounce: Duration,
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_owned())
+}
+
+impl ShaderWatcher {
+    pub fn new(debounce: Duration) -> Result<Self, Error> {
+        let (tx, rx) = mpsc::channel();
+
+        let watcher = notify::recommended_watcher(move |event| {
+            // The receiver is only gone if the watcher was dropped
+            let _ = tx.send(event);
+        })?;
+
+        Ok(Self {
+            watcher,
+            events: rx,
+            watched_dirs: HashSet::new(),
+            shaders: HashMap::new(),
+            pending: HashMap::new(),
+            debounce,
+        })
+    }
+
+    /// Compiles the shader (falling back if it fails to compile) and starts watching its files.
+    /// Replaces any shader registered under the same name.
+    pub fn register(
+        &mut self,
+        device: &wgpu::Device,
+        mut source: ShaderSource,
+    ) -> Result<(), Error> {
+        let (module, error) = super::create_or_fallback(device, &mut source);
+
+        if let Some(e) = error {
+            log::error!("Shader {} failed to compile: {}", source.name(), e);
+        }
+
+        let dependencies = self.watch_dependencies(&source)?;
+
+        self.shaders.insert(
+            source.name().to_owned(),
+            WatchedShader {
+                source,
+                module,
+                dependencies,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn watch_dependencies(&mut self, source: &ShaderSource) -> Result<Vec<PathBuf>, Error> {
+        let dependencies: Vec<_> = source.dependencies().iter().map(|p| absolute(p)).collect();
+
+        for path in &dependencies {
+            let Some(dir) = path.parent() else {
+                continue;
+            };
+
+            if self.watched_dirs.insert(dir.to_owned()) {
+                self.watcher
+                    .watch(dir, notify::RecursiveMode::NonRecursive)?;
+            }
+        }
+
+        Ok(dependencies)
+    }
+
+    /// Stops tracking the shader. Its directories stay watched.
+    pub fn unregister(&mut self, name: &str) -> Option<ShaderSource> {
+        self.pending.remove(name);
+        self.shaders.remove(name).map(|s| s.source)
+    }
+
+    pub fn module(&self, name: &str) -> Option<&wgpu::ShaderModule> {
+        self.shaders.get(name).map(|s| &s.module)
+    }
+
+    pub fn source(&self, name: &str) -> Option<&ShaderSource> {
+        self.shaders.get(name).map(|s| &s.source)
+    }
+
+    /// Processes file change events, and reloads and recompiles shaders whose files stopped
+    /// changing. Returns the names of the shaders that were recompiled, so that dependent
+    /// pipelines can be rebuilt. Meant to be called once per frame.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Vec<String> {
+        let now = Instant::now();
+
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    log::error!("Shader watcher error: {}", e);
+                    continue;
+                }
+            };
+
+            if !(event.kind.is_modify() || event.kind.is_create()) {
+                continue;
+            }
+
+            for (name, shader) in &self.shaders {
+                if event.paths.iter().any(|p| shader.dependencies.contains(p)) {
+                    self.pending.insert(name.clone(), now);
+                }
+            }
+        }
+
+        let ready: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, &time)| now.duration_since(time) >= self.debounce)
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let mut changed = Vec::new();
+
+        for name in ready {
+            self.pending.remove(&name);
+
+            let Some(mut shader) = self.shaders.remove(&name) else {
+                continue;
+            };
+
+            shader.source.reload();
+
+            let (module, error) = super::create_or_fallback(device, &mut shader.source);
+
+            match error {
+                Some(e) => log::error!("Shader {} failed to compile: {}", name, e),
+                None => log::info!("Reloaded shader {}", name),
+            }
+
+            shader.module = module;
+
+            // Dependencies may have changed with the new contents
+            match self.watch_dependencies(&shader.source) {
+                Ok(dependencies) => shader.dependencies = dependencies,
+                Err(e) => log::error!("Failed to watch files of shader {}: {}", name, e),
+            }
+
+            self.shaders.insert(name.clone(), shader);
+            changed.push(name);
+        }
+
+        changed
+    }
+}
@@ -130,9 +130,53 @@ pub fn copy(encoder: &mut wgpu::CommandEncoder, src: &wgpu::Texture, dst: &wgpu:
     encoder.copy_texture_to_texture(src.as_image_copy(), dst.as_image_copy(), src.size());
 }
 
+/// Returns how the texture is sampled. Combined depth-stencil formats are sampled through their
+/// depth aspect.
 pub fn sample_type(
     device: &wgpu::Device,
     texture: &wgpu::Texture,
 ) -> Option<wgpu::TextureSampleType> {
-    texture.format().sample_type(None, Some(device.features()))
+    format_sample_type(texture.format(), device.features())
+}
+
+fn format_sample_type(
+    format: wgpu::TextureFormat,
+    features: wgpu::Features,
+) -> Option<wgpu::TextureSampleType> {
+    let aspect = format
+        .has_depth_aspect()
+        .then_some(wgpu::TextureAspect::DepthOnly);
+
+    format.sample_type(aspect, Some(features))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_stencil_sample_type() {
+        let features = wgpu::Features::DEPTH32FLOAT_STENCIL8;
+
+        for format in [
+            wgpu::TextureFormat::Depth24PlusStencil8,
+            wgpu::TextureFormat::Depth32FloatStencil8,
+            wgpu::TextureFormat::Depth32Float,
+        ] {
+            assert_eq!(
+                format_sample_type(format, features),
+                Some(wgpu::TextureSampleType::Depth),
+                "{format:?}"
+            );
+        }
+
+        assert_eq!(
+            format_sample_type(wgpu::TextureFormat::Rgba8Unorm, features),
+            Some(wgpu::TextureSampleType::Float { filterable: true })
+        );
+        assert_eq!(
+            format_sample_type(wgpu::TextureFormat::Stencil8, features),
+            Some(wgpu::TextureSampleType::Uint)
+        );
+    }
 }
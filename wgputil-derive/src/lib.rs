@@ -0,0 +1,559 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, Data, DeriveInput, Fields, Ident, LitInt, Token,
+};
+
+/// Implements `wgputil::binding::AsBindGroup` for a struct with named fields. Every annotated
+/// field is bound at the given index, and indices must be sequential starting from 0.
+///
+/// ```ignore
+/// #[derive(BindGroup)]
+/// struct BlurBindings<'a> {
+///     #[uniform(0)]
+///     params: &'a wgpu::Buffer,
+///     #[texture(1, sample = float, dim = d2)]
+///     input: &'a wgpu::TextureView,
+///     #[sampler(2)]
+///     sampler: &'a wgpu::Sampler,
+///     #[storage_texture(3, format = Rgba16Float, access = write, dim = d2)]
+///     output: &'a wgpu::TextureView,
+/// }
+/// ```
+///
+/// Supported attributes:
+/// - `#[uniform(i)]` on a `wgpu::Buffer`
+/// - `#[storage(i)]` or `#[storage(i, read_only)]` on a `wgpu::Buffer`
+/// - `#[texture(i, sample = float | unfilterable_float | sint | uint | depth, dim = d1 | d2 | d2_array | cube | cube_array | d3)]`,
+///   optionally with `multisampled`, on a `wgpu::TextureView`
+/// - `#[storage_texture(i, format = <wgpu::TextureFormat variant>, access = read | write | read_write | atomic, dim = ...)]`
+///   on a `wgpu::TextureView`
+/// - `#[sampler(i)]`, `#[sampler(i, non_filtering)]` or `#[sampler(i, comparison)]` on a `wgpu::Sampler`
+#[proc_macro_derive(
+    BindGroup,
+    attributes(uniform, storage, texture, storage_texture, sampler)
+)]
+pub fn derive_bind_group(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Arguments of a binding attribute: an index followed by flags and `key = value` pairs.
+struct BindingArgs {
+    index: LitInt,
+    flags: Vec<Ident>,
+    values: Vec<(Ident, Ident)>,
+}
+
+impl Parse for BindingArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let index = input.parse()?;
+
+        let mut flags = Vec::new();
+        let mut values = Vec::new();
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+
+            // Allow a trailing comma
+            if input.is_empty() {
+                break;
+            }
+
+            let key: Ident = input.parse()?;
+
+            if input.peek(Token![=]) {
+                input.parse::<Token![=]>()?;
+                values.push((key, input.parse()?));
+            } else {
+                flags.push(key);
+            }
+        }
+
+        Ok(Self {
+            index,
+            flags,
+            values,
+        })
+    }
+}
+
+impl BindingArgs {
+    fn has_flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|f| f == name)
+    }
+
+    fn value(&self, name: &str) -> syn::Result<&Ident> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v)
+            .ok_or_else(|| syn::Error::new(self.index.span(), format!("missing `{name} = ...`")))
+    }
+
+    fn check_known(&self, flags: &[&str], values: &[&str]) -> syn::Result<()> {
+        if let Some(flag) = self.flags.iter().find(|f| !flags.iter().any(|k| *f == k)) {
+            return Err(syn::Error::new(flag.span(), "unknown flag"));
+        }
+
+        if let Some((key, _)) = self
+            .values
+            .iter()
+            .find(|(k, _)| !values.iter().any(|v| k == v))
+        {
+            return Err(syn::Error::new(key.span(), "unknown argument"));
+        }
+
+        Ok(())
+    }
+}
+
+struct Binding {
+    index: u32,
+    span: Span,
+
+    /// Expression evaluating to a `wgpu::BindingType`.
+    binding_type: TokenStream,
+    /// Expression evaluating to a `wgputil::binding::BindingEntry`, given `self`.
+    entry: TokenStream,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "BindGroup can only be derived for structs",
+        ));
+    };
+
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "BindGroup can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut bindings = Vec::new();
+
+    for field in &fields.named {
+        let name = field.ident.as_ref().unwrap();
+
+        for attr in &field.attrs {
+            let Some(kind) = attr.path().get_ident().map(|i| i.to_string()) else {
+                continue;
+            };
+
+            let args = || attr.parse_args::<BindingArgs>();
+
+            let binding = match kind.as_str() {
+                "uniform" => uniform(name, args()?)?,
+                "storage" => storage(name, args()?)?,
+                "texture" => texture(name, args()?)?,
+                "storage_texture" => storage_texture(name, args()?)?,
+                "sampler" => sampler(name, args()?)?,
+                _ => continue,
+            };
+
+            bindings.push(binding);
+        }
+    }
+
+    bindings.sort_by_key(|b| b.index);
+
+    // The bind group is created with sequential indices, so they must line up exactly
+    for (expected, binding) in (0..).zip(&bindings) {
+        if binding.index != expected {
+            return Err(syn::Error::new(
+                binding.span,
+                format!("expected binding index {expected}, indices must be sequential from 0"),
+            ));
+        }
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let layout_entries = bindings.iter().map(|b| {
+        let index = b.index as usize;
+        let binding_type = &b.binding_type;
+
+        quote! { ::wgputil::binding::layout_entry(#index, #binding_type, None) }
+    });
+
+    let entries = bindings.iter().map(|b| &b.entry);
+
+    Ok(quote! {
+        impl #impl_generics ::wgputil::binding::AsBindGroup for #ident #ty_generics #where_clause {
+            fn layout_entries() -> ::std::vec::Vec<::wgputil::__private::wgpu::BindGroupLayoutEntry> {
+                ::std::vec![#(#layout_entries),*]
+            }
+
+            fn binding_entries(&self) -> ::std::vec::Vec<::wgputil::binding::BindingEntry<'_>> {
+                ::std::vec![#(#entries),*]
+            }
+        }
+    })
+}
+
+fn index(args: &BindingArgs) -> syn::Result<u32> {
+    args.index.base10_parse()
+}
+
+fn uniform(name: &Ident, args: BindingArgs) -> syn::Result<Binding> {
+    args.check_known(&[], &[])?;
+
+    let binding_type = quote! {
+        ::wgputil::__private::wgpu::BindingType::Buffer {
+            ty: ::wgputil::__private::wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }
+    };
+
+    Ok(Binding {
+        index: index(&args)?,
+        span: args.index.span(),
+        binding_type,
+        entry: quote! { ::wgputil::binding::bind_buffer_uniform(&self.#name) },
+    })
+}
+
+fn storage(name: &Ident, args: BindingArgs) -> syn::Result<Binding> {
+    args.check_known(&["read_only"], &[])?;
+
+    let read_only = args.has_flag("read_only");
+
+    let binding_type = quote! {
+        ::wgputil::__private::wgpu::BindingType::Buffer {
+            ty: ::wgputil::__private::wgpu::BufferBindingType::Storage { read_only: #read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }
+    };
+
+    Ok(Binding {
+        index: index(&args)?,
+        span: args.index.span(),
+        binding_type,
+        entry: quote! { ::wgputil::binding::bind_buffer_storage(&self.#name, #read_only) },
+    })
+}
+
+fn view_dimension(args: &BindingArgs) -> syn::Result<TokenStream> {
+    let dim = args.value("dim")?;
+
+    let variant = match dim.to_string().as_str() {
+        "d1" => quote!(D1),
+        "d2" => quote!(D2),
+        "d2_array" => quote!(D2Array),
+        "cube" => quote!(Cube),
+        "cube_array" => quote!(CubeArray),
+        "d3" => quote!(D3),
+        _ => return Err(syn::Error::new(dim.span(), "unknown view dimension")),
+    };
+
+    Ok(quote! { ::wgputil::__private::wgpu::TextureViewDimension::#variant })
+}
+
+fn texture(name: &Ident, args: BindingArgs) -> syn::Result<Binding> {
+    args.check_known(&["multisampled"], &["sample", "dim"])?;
+
+    let sample = args.value("sample")?;
+
+    let sample_type = match sample.to_string().as_str() {
+        "float" => {
+            quote! { ::wgputil::__private::wgpu::TextureSampleType::Float { filterable: true } }
+        }
+        "unfilterable_float" => {
+            quote! { ::wgputil::__private::wgpu::TextureSampleType::Float { filterable: false } }
+        }
+        "sint" => quote! { ::wgputil::__private::wgpu::TextureSampleType::Sint },
+        "uint" => quote! { ::wgputil::__private::wgpu::TextureSampleType::Uint },
+        "depth" => quote! { ::wgputil::__private::wgpu::TextureSampleType::Depth },
+        _ => return Err(syn::Error::new(sample.span(), "unknown sample type")),
+    };
+
+    let view_dimension = view_dimension(&args)?;
+    let multisampled = args.has_flag("multisampled");
+
+    let binding_type = quote! {
+        ::wgputil::__private::wgpu::BindingType::Texture {
+            sample_type: #sample_type,
+            view_dimension: #view_dimension,
+            multisampled: #multisampled,
+        }
+    };
+
+    let function = match multisampled {
+        true => quote!(bind_texture_multisampled),
+        false => quote!(bind_texture),
+    };
+
+    Ok(Binding {
+        index: index(&args)?,
+        span: args.index.span(),
+        binding_type,
+        entry: quote! {
+            ::wgputil::binding::#function(&self.#name, #sample_type, #view_dimension)
+        },
+    })
+}
+
+fn storage_texture(name: &Ident, args: BindingArgs) -> syn::Result<Binding> {
+    args.check_known(&[], &["format", "access", "dim"])?;
+
+    let format = args.value("format")?;
+    let access = args.value("access")?;
+
+    let access = match access.to_string().as_str() {
+        "read" => quote!(ReadOnly),
+        "write" => quote!(WriteOnly),
+        "read_write" => quote!(ReadWrite),
+        "atomic" => quote!(Atomic),
+        _ => return Err(syn::Error::new(access.span(), "unknown storage access")),
+    };
+
+    let format = quote! { ::wgputil::__private::wgpu::TextureFormat::#format };
+    let access = quote! { ::wgputil::__private::wgpu::StorageTextureAccess::#access };
+    let view_dimension = view_dimension(&args)?;
+
+    let binding_type = quote! {
+        ::wgputil::__private::wgpu::BindingType::StorageTexture {
+            access: #access,
+            format: #format,
+            view_dimension: #view_dimension,
+        }
+    };
+
+    Ok(Binding {
+        index: index(&args)?,
+        span: args.index.span(),
+        binding_type,
+        entry: quote! {
+            ::wgputil::binding::bind_storage_texture(&self.#name, #format, #view_dimension, #access)
+        },
+    })
+}
+
+fn sampler(name: &Ident, args: BindingArgs) -> syn::Result<Binding> {
+    args.check_known(&["non_filtering", "comparison"], &[])?;
+
+    let variant = match (args.has_flag("non_filtering"), args.has_flag("comparison")) {
+        (false, false) => quote!(Filtering),
+        (true, false) => quote!(NonFiltering),
+        (false, true) => quote!(Comparison),
+        (true, true) => {
+            return Err(syn::Error::new(
+                args.index.span(),
+                "a sampler can't be both non-filtering and comparison",
+            ))
+        }
+    };
+
+    let sampler_type = quote! { ::wgputil::__private::wgpu::SamplerBindingType::#variant };
+
+    Ok(Binding {
+        index: index(&args)?,
+        span: args.index.span(),
+        binding_type: quote! { ::wgputil::__private::wgpu::BindingType::Sampler(#sampler_type) },
+        entry: quote! { ::wgputil::binding::bind_sampler(&self.#name, #sampler_type) },
+    })
+}
+
+/// Implements `wgputil::layout::WgslLayout` for a struct with named fields, computing its WGSL
+/// size, alignment and member offsets from the fields' own `WgslLayout` implementations.
+#[proc_macro_derive(WgslLayout)]
+pub fn derive_wgsl_layout(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_wgsl_layout(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_wgsl_layout(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "WgslLayout can only be derived for structs",
+        ));
+    };
+
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "WgslLayout can only be derived for structs with named fields",
+        ));
+    };
+
+    if fields.named.is_empty() {
+        return Err(syn::Error::new_spanned(
+            input,
+            "WGSL structs must have at least one member",
+        ));
+    }
+
+    let layout = quote!(::wgputil::layout);
+
+    let types: Vec<_> = fields.named.iter().map(|f| &f.ty).collect();
+    let names: Vec<_> = fields
+        .named
+        .iter()
+        .map(|f| f.ident.as_ref().unwrap())
+        .collect();
+    let name_strings = names.iter().map(|n| n.to_string());
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #layout::WgslLayout for #ident #ty_generics #where_clause {
+            const WGSL_ALIGN: u64 = {
+                let align = 0;
+                #(let align = #layout::max(align, <#types as #layout::WgslLayout>::WGSL_ALIGN);)*
+                align
+            };
+
+            const WGSL_SIZE: u64 = {
+                let offset = 0;
+                #(
+                    let offset = #layout::round_up(<#types as #layout::WgslLayout>::WGSL_ALIGN, offset)
+                        + <#types as #layout::WgslLayout>::WGSL_SIZE;
+                )*
+                #layout::round_up(<Self as #layout::WgslLayout>::WGSL_ALIGN, offset)
+            };
+
+            const WGSL_UNIFORM_ALIGN: u64 = {
+                let align = 0;
+                #(let align = #layout::max(align, <#types as #layout::WgslLayout>::WGSL_UNIFORM_ALIGN);)*
+                #layout::round_up(16, align)
+            };
+
+            const WGSL_UNIFORM_SIZE: u64 = {
+                let offset = 0;
+                #(
+                    let offset = #layout::round_up(<#types as #layout::WgslLayout>::WGSL_UNIFORM_ALIGN, offset)
+                        + <#types as #layout::WgslLayout>::WGSL_UNIFORM_MEMBER_SIZE;
+                )*
+                #layout::round_up(<Self as #layout::WgslLayout>::WGSL_ALIGN, offset)
+            };
+
+            const WGSL_UNIFORM_MEMBER_SIZE: u64 =
+                #layout::round_up(16, <Self as #layout::WgslLayout>::WGSL_UNIFORM_SIZE);
+
+            const WGSL_UNIFORM_COMPATIBLE: bool =
+                true #(&& <#types as #layout::WgslLayout>::WGSL_UNIFORM_COMPATIBLE)*;
+
+            fn members() -> ::std::vec::Vec<#layout::MemberLayout> {
+                let mut wgsl_offset = 0;
+                let mut wgsl_uniform_offset = 0;
+                let mut members = ::std::vec::Vec::new();
+
+                #(
+                    wgsl_offset = #layout::round_up(<#types as #layout::WgslLayout>::WGSL_ALIGN, wgsl_offset);
+                    wgsl_uniform_offset = #layout::round_up(
+                        <#types as #layout::WgslLayout>::WGSL_UNIFORM_ALIGN,
+                        wgsl_uniform_offset,
+                    );
+
+                    members.push(#layout::MemberLayout {
+                        name: #name_strings,
+                        size: ::std::mem::size_of::<#types>() as u64,
+                        rust_offset: ::std::mem::offset_of!(Self, #names) as u64,
+                        wgsl_offset,
+                        wgsl_uniform_offset,
+                        uniform_compatible: <#types as #layout::WgslLayout>::WGSL_UNIFORM_COMPATIBLE,
+                    });
+
+                    wgsl_offset += <#types as #layout::WgslLayout>::WGSL_SIZE;
+                    wgsl_uniform_offset += <#types as #layout::WgslLayout>::WGSL_UNIFORM_MEMBER_SIZE;
+                )*
+
+                members
+            }
+        }
+    })
+}
+
+/// Implements `wgputil::buffer::Vertex` for a struct with named fields. Shader locations are
+/// assigned in field order starting from 0, and formats come from `VertexAttributeType`.
+///
+/// Fields can be annotated with `#[vertex(location = n)]` to set the location (following fields
+/// continue from there), or `#[vertex(format = Unorm8x4)]` to override the format.
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_vertex(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_vertex(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "Vertex can only be derived for structs",
+        ));
+    };
+
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "Vertex can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut next_location = 0u32;
+    let mut attributes = Vec::new();
+
+    for field in &fields.named {
+        let name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+
+        let mut location = next_location;
+
+        let mut format = quote! { <#ty as ::wgputil::buffer::VertexAttributeType>::FORMAT };
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("vertex")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("location") {
+                    location = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                    Ok(())
+                } else if meta.path.is_ident("format") {
+                    let variant: Ident = meta.value()?.parse()?;
+                    format = quote! { ::wgputil::__private::wgpu::VertexFormat::#variant };
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `location` or `format`"))
+                }
+            })?;
+        }
+
+        attributes.push(quote! {
+            ::wgputil::__private::wgpu::VertexAttribute {
+                format: #format,
+                offset: ::std::mem::offset_of!(Self, #name) as u64,
+                shader_location: #location,
+            }
+        });
+
+        next_location = location + 1;
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::wgputil::buffer::Vertex for #ident #ty_generics #where_clause {
+            const ATTRIBUTES: &'static [::wgputil::__private::wgpu::VertexAttribute] = &[#(#attributes),*];
+        }
+    })
+}